@@ -0,0 +1,138 @@
+use std::io;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use transactions::{
+    core::{ledger::Ledger, transaction::Transaction},
+    dump_to_csv, read_from_file, read_from_reader, write_workload, CsvTransaction, Workload,
+    WorkloadConfig, WorkloadRow,
+};
+
+const ROWS: u64 = 100_000;
+
+fn deposit_heavy() -> WorkloadConfig {
+    WorkloadConfig {
+        seed: 1,
+        transactions: ROWS,
+        withdrawal_rate: 0.1,
+        dispute_rate: 0.01,
+        settle_rate: 0.01,
+        malformed_rate: 0.0,
+        ..Default::default()
+    }
+}
+
+fn dispute_heavy() -> WorkloadConfig {
+    WorkloadConfig {
+        seed: 2,
+        transactions: ROWS,
+        withdrawal_rate: 0.1,
+        dispute_rate: 0.3,
+        settle_rate: 0.3,
+        chargeback_rate: 0.1,
+        malformed_rate: 0.0,
+        ..Default::default()
+    }
+}
+
+fn csv_rows(config: WorkloadConfig) -> Vec<CsvTransaction> {
+    Workload::new(config)
+        .filter_map(|row| match row {
+            WorkloadRow::Transaction(tx) => Some(tx),
+            WorkloadRow::Malformed(_) => None,
+        })
+        .collect()
+}
+
+fn transactions(config: WorkloadConfig) -> Vec<Transaction> {
+    csv_rows(config)
+        .into_iter()
+        .filter_map(|x| Transaction::try_from(x).ok())
+        .collect()
+}
+
+fn run(txs: &[Transaction]) -> Ledger {
+    let mut ledger = Ledger::default();
+    for tx in txs {
+        let _ = ledger.handle_transaction(tx);
+    }
+    ledger
+}
+
+fn parsing(c: &mut Criterion) {
+    // removed when dropped, concurrent runs get their own file
+    let input = tempfile::NamedTempFile::new().unwrap();
+    let path = input.path();
+    write_workload(
+        WorkloadConfig {
+            malformed_rate: 0.01,
+            ..deposit_heavy()
+        },
+        input.as_file(),
+    )
+    .unwrap();
+    let in_memory = std::fs::read(path).unwrap();
+
+    let mut group = c.benchmark_group("parsing");
+    group.throughput(Throughput::Elements(ROWS));
+    group.bench_function("read_from_file", |b| {
+        b.iter(|| read_from_file(path).unwrap().count())
+    });
+    group.bench_function("read_from_reader", |b| {
+        b.iter(|| read_from_reader(in_memory.as_slice()).unwrap().count())
+    });
+    group.bench_function("transaction_try_from", |b| {
+        b.iter_batched(
+            || csv_rows(deposit_heavy()),
+            |rows| {
+                rows.into_iter()
+                    .filter_map(|x| Transaction::try_from(x).ok())
+                    .count()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn ledger(c: &mut Criterion) {
+    let mut group = c.benchmark_group("handle_transaction");
+    group.throughput(Throughput::Elements(ROWS));
+    let deposits = transactions(deposit_heavy());
+    group.bench_function("deposit_heavy", |b| b.iter(|| run(&deposits)));
+    let disputes = transactions(dispute_heavy());
+    group.bench_function("dispute_heavy", |b| b.iter(|| run(&disputes)));
+    group.finish();
+}
+
+fn growth(c: &mut Criterion) {
+    const GROWTH_ROWS: u64 = 2_000_000;
+    let mut group = c.benchmark_group("growth");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(GROWTH_ROWS));
+    let txs = transactions(WorkloadConfig {
+        seed: 3,
+        clients: u16::MAX,
+        transactions: GROWTH_ROWS,
+        ..deposit_heavy()
+    });
+    group.bench_function("all_clients_2m_txs", |b| b.iter(|| run(&txs)));
+    group.finish();
+}
+
+fn dump(c: &mut Criterion) {
+    let ledger = run(&transactions(WorkloadConfig {
+        seed: 4,
+        clients: u16::MAX,
+        transactions: 500_000,
+        ..deposit_heavy()
+    }));
+    let mut group = c.benchmark_group("dump_to_csv");
+    group.throughput(Throughput::Elements(ledger.entries().count() as u64));
+    group.bench_function("all_clients", |b| {
+        b.iter(|| dump_to_csv(ledger.rows(), ledger.report_columns(), io::sink()).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parsing, ledger, growth, dump);
+criterion_main!(benches);
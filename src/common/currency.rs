@@ -0,0 +1,90 @@
+use std::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+/// Three letter currency code (`USD`, `EUR`, ...), stored upper case.
+/// The default value is the unnamed currency of rows without a currency column,
+/// it's written as an empty string.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Currency([u8; 3]);
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("Currency code must be empty or three ASCII letters")]
+pub struct InvalidCurrency;
+
+impl Currency {
+    pub fn is_default(&self) -> bool {
+        *self == Currency::default()
+    }
+
+    pub fn as_str(&self) -> &str {
+        if self.is_default() {
+            return "";
+        }
+        std::str::from_utf8(&self.0).expect("currency codes are ASCII")
+    }
+}
+
+impl FromStr for Currency {
+    type Err = InvalidCurrency;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Ok(Currency::default());
+        }
+        let code: [u8; 3] = s.as_bytes().try_into().map_err(|_| InvalidCurrency)?;
+        if !code.iter().all(u8::is_ascii_alphabetic) {
+            return Err(InvalidCurrency);
+        }
+        Ok(Currency(code.map(|x| x.to_ascii_uppercase())))
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+struct CurrencyVisitor;
+
+impl de::Visitor<'_> for CurrencyVisitor {
+    type Value = Currency;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an empty string or a three letter currency code")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse()
+            .map_err(|e: InvalidCurrency| E::custom(e.to_string()))
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(CurrencyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Currency, InvalidCurrency};
+
+    #[test]
+    fn parse() {
+        let eur: Currency = "eur".parse().unwrap();
+        assert_eq!(eur.to_string(), "EUR");
+        assert_eq!("".parse::<Currency>(), Ok(Currency::default()));
+        assert_eq!(Currency::default().to_string(), "");
+        assert_eq!("EURO".parse::<Currency>(), Err(InvalidCurrency));
+        assert_eq!("E1R".parse::<Currency>(), Err(InvalidCurrency));
+    }
+}
@@ -1,4 +1,6 @@
 pub mod amount;
+pub mod currency;
 
 pub type TxId = u32;
 pub type ClientId = u16;
+pub type Timestamp = chrono::DateTime<chrono::Utc>;
@@ -1,17 +1,14 @@
-use crate::{Amount, ClientId, CsvAccount};
+use std::collections::BTreeMap;
 
-#[derive(Debug, Clone, Copy)]
+use crate::{Amount, ClientId, CsvAccount, Currency};
+
+#[derive(Default, Debug, Clone, Copy)]
 pub enum AccountStatus {
+    #[default]
     Active,
     Frozen,
 }
 
-impl Default for AccountStatus {
-    fn default() -> Self {
-        Self::Active
-    }
-}
-
 #[derive(Default, Debug, PartialEq, Eq)]
 pub struct Balance {
     pub(crate) amount: Amount,
@@ -24,48 +21,126 @@ impl Balance {
     }
 }
 
+/// Funds of an account in a single currency.
 #[derive(Default, Debug)]
-pub struct Account {
+pub struct Wallet {
     pub available: Balance,
     pub held: Balance,
+    /// withdrawals and outgoing transfers waiting for review
+    pub reserved: Balance,
+    /// deposits waiting for review, not part of the balance until approved
+    pub incoming: Amount,
+}
+
+impl Wallet {
+    pub fn total(&self) -> Amount {
+        self.available.amount + self.held.amount + self.reserved.amount
+    }
+    /// how far `available` is below zero
+    pub fn credit_used(&self) -> Amount {
+        (-self.available.amount).max(Amount::ZERO)
+    }
+}
+
+/// Client account, holds a [`Wallet`] for every currency the client ever deposited.
+/// The status is shared: a chargeback in any currency freezes the whole account.
+#[derive(Default, Debug)]
+pub struct Account {
+    pub wallets: BTreeMap<Currency, Wallet>,
     pub status: AccountStatus,
 }
 
 impl Account {
-    pub fn total(&self) -> Amount {
-        self.available.amount + self.held.amount
+    pub fn wallet(&self, currency: &Currency) -> Option<&Wallet> {
+        self.wallets.get(currency)
+    }
+    fn wallet_mut(&mut self, currency: Currency) -> &mut Wallet {
+        self.wallets.entry(currency).or_default()
     }
-    pub fn deposit(&mut self, source: &mut Balance, amount: Amount) {
-        source.transfer(&mut self.available, amount)
+    /// total of a single currency, zero if the client never held it
+    pub fn total(&self, currency: &Currency) -> Amount {
+        self.wallet(currency).map_or(Amount::ZERO, Wallet::total)
     }
-    pub fn withdraw(&mut self, target: &mut Balance, amount: Amount) {
+    pub fn deposit(&mut self, source: &mut Balance, currency: Currency, amount: Amount) {
+        source.transfer(&mut self.wallet_mut(currency).available, amount)
+    }
+    /// `credit` is how far `available` may go below zero
+    pub fn withdraw(
+        &mut self,
+        target: &mut Balance,
+        currency: Currency,
+        amount: Amount,
+        credit: Amount,
+    ) {
         // if a client tries to withdraw more than available we just ignore the operation
-        if self.available.amount >= amount {
-            self.available.transfer(target, amount)
+        if let Some(wallet) = self.wallets.get_mut(&currency) {
+            if wallet.available.amount + credit >= amount {
+                wallet.available.transfer(target, amount)
+            }
         }
     }
-    pub fn hold(&mut self, amount: Amount) {
-        self.available.transfer(&mut self.held, amount)
+    pub fn hold(&mut self, currency: Currency, amount: Amount) {
+        let wallet = self.wallet_mut(currency);
+        wallet.available.transfer(&mut wallet.held, amount)
+    }
+
+    pub fn resolve(&mut self, currency: Currency, amount: Amount) {
+        let wallet = self.wallet_mut(currency);
+        wallet.held.transfer(&mut wallet.available, amount)
+    }
+
+    /// sets funds of a transaction waiting for review aside
+    pub fn reserve(&mut self, currency: Currency, amount: Amount) {
+        let wallet = self.wallet_mut(currency);
+        wallet.available.transfer(&mut wallet.reserved, amount)
+    }
+
+    /// gives reserved funds back once the review is over
+    pub fn release(&mut self, currency: Currency, amount: Amount) {
+        let wallet = self.wallet_mut(currency);
+        wallet.reserved.transfer(&mut wallet.available, amount)
     }
 
-    pub fn resolve(&mut self, amount: Amount) {
-        self.held.transfer(&mut self.available, amount)
+    /// tracks deposits waiting for review, negative amounts remove them
+    pub fn expect_deposit(&mut self, currency: Currency, amount: Amount) {
+        self.wallet_mut(currency).incoming += amount;
     }
 
-    pub fn chargeback(&mut self, target: &mut Balance, amount: Amount) {
-        self.held.transfer(target, amount);
+    pub fn chargeback(&mut self, target: &mut Balance, currency: Currency, amount: Amount) {
+        self.wallet_mut(currency).held.transfer(target, amount);
         self.status = AccountStatus::Frozen;
     }
-}
 
-impl From<(ClientId, &Account)> for CsvAccount {
-    fn from((id, account): (ClientId, &Account)) -> Self {
-        CsvAccount {
-            client: id,
-            available: account.available.amount,
-            held: account.held.amount,
-            total: account.available.amount + account.held.amount,
-            locked: !matches!(account.status, AccountStatus::Active),
-        }
+    /// moves funds from the available balance to `target` even if that leaves it negative
+    pub fn debit(&mut self, target: &mut Balance, currency: Currency, amount: Amount) {
+        self.wallet_mut(currency).available.transfer(target, amount)
+    }
+
+    /// moves a fee from the available balance to `target`
+    pub fn pay_fee(&mut self, target: &mut Balance, currency: Currency, fee: Amount) {
+        self.wallet_mut(currency).available.transfer(target, fee)
+    }
+
+    /// moves posted interest between `source` and the available balance, negative amounts
+    /// are charged to the account
+    pub fn post_interest(&mut self, source: &mut Balance, currency: Currency, amount: Amount) {
+        source.transfer(&mut self.wallet_mut(currency).available, amount)
+    }
+
+    /// one output row per currency of the account
+    pub fn rows(&self, id: ClientId) -> impl Iterator<Item = CsvAccount> + '_ {
+        self.wallets
+            .iter()
+            .map(move |(currency, wallet)| CsvAccount {
+                client: id,
+                available: wallet.available.amount,
+                held: wallet.held.amount,
+                total: wallet.total(),
+                locked: !matches!(self.status, AccountStatus::Active),
+                currency: *currency,
+                credit_used: wallet.credit_used(),
+                pending_withdrawals: wallet.reserved.amount,
+                pending_deposits: wallet.incoming,
+            })
     }
 }
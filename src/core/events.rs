@@ -0,0 +1,502 @@
+//! Domain events emitted by the ledger for every change of a client balance or status.
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    account::AccountStatus,
+    ledger::{DepositOutcome, Ledger},
+    transaction::SettlementTransaction,
+};
+use crate::{Amount, ClientId, Currency, Timestamp};
+
+/// Amounts written so they read back to the same value. `Display` of [`Amount`] drops the
+/// last bit of some values, e.g. what's left after a partial dispute, those are written with
+/// all of their (at most 16) decimals.
+mod exact_amount {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use crate::Amount;
+
+    pub fn serialize<S: Serializer>(amount: &Amount, serializer: S) -> Result<S::Ok, S::Error> {
+        let shown = amount.to_string();
+        if Amount::from_str(&shown).is_ok_and(|x| x == *amount) {
+            return serializer.serialize_str(&shown);
+        }
+        let bits = amount.to_bits().unsigned_abs();
+        let sign = if *amount < Amount::ZERO { "-" } else { "" };
+        // 2^-16 = 0.0000152587890625
+        let fraction = format!("{:016}", (bits & 0xffff) * 152_587_890_625);
+        let exact = format!("{sign}{}.{}", bits >> 16, fraction.trim_end_matches('0'));
+        serializer.serialize_str(&exact)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Amount, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Amount::from_str(&s).map_err(de::Error::custom)
+    }
+}
+
+/// What changed, amounts are positive unless noted otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// first transaction of the client
+    AccountOpened {
+        client: ClientId,
+    },
+    /// credited to `available` by a deposit or an incoming or returned transfer
+    FundsDeposited {
+        client: ClientId,
+        currency: Currency,
+        #[serde(with = "exact_amount")]
+        amount: Amount,
+    },
+    /// debited from `available` by a withdrawal or an outgoing transfer, a reversed chargeback
+    /// of a transfer takes it back from the sender
+    FundsWithdrawn {
+        client: ClientId,
+        currency: Currency,
+        #[serde(with = "exact_amount")]
+        amount: Amount,
+    },
+    /// moved from `available` to `held` by a dispute
+    FundsHeld {
+        client: ClientId,
+        currency: Currency,
+        #[serde(with = "exact_amount")]
+        amount: Amount,
+        /// sender of a disputed transfer, `client` received it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sender: Option<ClientId>,
+    },
+    /// moved back from `held` to `available` by a resolve
+    FundsReleased {
+        client: ClientId,
+        currency: Currency,
+        #[serde(with = "exact_amount")]
+        amount: Amount,
+    },
+    /// taken from `held` by a chargeback
+    ChargedBack {
+        client: ClientId,
+        currency: Currency,
+        #[serde(with = "exact_amount")]
+        amount: Amount,
+        /// sender of a charged back transfer, the funds go back to it instead of the ledger
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sender: Option<ClientId>,
+        /// nothing of the transaction was charged back before
+        first: bool,
+    },
+    /// credited back to `available` by a chargeback reversal
+    ChargebackReversed {
+        client: ClientId,
+        currency: Currency,
+        #[serde(with = "exact_amount")]
+        amount: Amount,
+        /// see [`Event::ChargedBack`]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sender: Option<ClientId>,
+        /// nothing of the transaction is charged back anymore
+        complete: bool,
+    },
+    AccountFrozen {
+        client: ClientId,
+    },
+    /// by a chargeback reversal, see [`super::lifecycle::UnfreezePolicy`]
+    AccountUnfrozen {
+        client: ClientId,
+    },
+    /// moved from `available` to the house revenue
+    FeeCharged {
+        client: ClientId,
+        currency: Currency,
+        #[serde(with = "exact_amount")]
+        amount: Amount,
+    },
+    /// moved between the house interest balance and `available`, negative amounts are charged
+    /// to the client
+    InterestPosted {
+        client: ClientId,
+        currency: Currency,
+        #[serde(with = "exact_amount")]
+        amount: Amount,
+    },
+    /// moved from `available` to `reserved` by a withdrawal or transfer waiting for review
+    FundsReserved {
+        client: ClientId,
+        currency: Currency,
+        #[serde(with = "exact_amount")]
+        amount: Amount,
+    },
+    /// moved back from `reserved` to `available` once the review is over
+    ReservationReleased {
+        client: ClientId,
+        currency: Currency,
+        #[serde(with = "exact_amount")]
+        amount: Amount,
+    },
+    /// a deposit is waiting for review
+    DepositPending {
+        client: ClientId,
+        currency: Currency,
+        #[serde(with = "exact_amount")]
+        amount: Amount,
+    },
+    /// a deposit waiting for review was approved or declined, an approved one is deposited
+    /// right after
+    DepositReviewed {
+        client: ClientId,
+        currency: Currency,
+        #[serde(with = "exact_amount")]
+        amount: Amount,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEvent {
+    /// [`Ledger::sequence`] of the transaction that caused it
+    pub seq: u64,
+    /// latest timestamp seen by the ledger at the time
+    pub timestamp: Option<Timestamp>,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Receives the events of a ledger in the order they happen. Closures taking a
+/// `&LedgerEvent` are subscribers as well.
+pub trait Subscriber: Send {
+    fn notify(&mut self, event: &LedgerEvent);
+
+    /// Called by [`Ledger::flush_subscribers`], reports errors that couldn't be returned
+    /// by [`Subscriber::notify`].
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<F: FnMut(&LedgerEvent) + Send> Subscriber for F {
+    fn notify(&mut self, event: &LedgerEvent) {
+        self(event)
+    }
+}
+
+/// Writes every event as a line of JSON (NDJSON).
+pub struct JsonLines<W> {
+    writer: W,
+    /// the first write error, nothing is written after it
+    error: Option<io::Error>,
+}
+
+impl<W: Write + Send> JsonLines<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            error: None,
+        }
+    }
+}
+
+impl<W: Write + Send> Subscriber for JsonLines<W> {
+    fn notify(&mut self, event: &LedgerEvent) {
+        if self.error.is_some() {
+            return;
+        }
+        let written = serde_json::to_writer(&mut self.writer, event)
+            .map_err(io::Error::from)
+            .and_then(|_| self.writer.write_all(b"\n"));
+        if let Err(err) = written {
+            self.error = Some(err);
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+impl Ledger {
+    pub fn with_subscriber(mut self, subscriber: impl Subscriber + 'static) -> Self {
+        self.subscribers.push(Box::new(subscriber));
+        self
+    }
+
+    pub(super) fn emit(&mut self, event: Event) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        let event = LedgerEvent {
+            seq: self.sequence,
+            timestamp: self.clock,
+            event,
+        };
+        for subscriber in &mut self.subscribers {
+            subscriber.notify(&event);
+        }
+    }
+
+    /// Emits what a settlement by `client` changed, `holder` received the deposit or transfer.
+    pub(super) fn emit_settlement(
+        &mut self,
+        client: ClientId,
+        holder: ClientId,
+        tx: &SettlementTransaction,
+        prev: &DepositOutcome,
+        deposit: &DepositOutcome,
+        was_frozen: bool,
+    ) {
+        let currency = deposit.currency;
+        let sender = (holder != client).then_some(client);
+        match tx {
+            SettlementTransaction::Dispute => self.emit(Event::FundsHeld {
+                client: holder,
+                currency,
+                amount: deposit.disputed - prev.disputed,
+                sender,
+            }),
+            SettlementTransaction::Resolve => self.emit(Event::FundsReleased {
+                client: holder,
+                currency,
+                amount: prev.disputed - deposit.disputed,
+            }),
+            SettlementTransaction::Chargeback => {
+                let amount = deposit.charged_back - prev.charged_back;
+                self.emit(Event::ChargedBack {
+                    client: holder,
+                    currency,
+                    amount,
+                    sender,
+                    first: prev.charged_back == Amount::ZERO,
+                });
+                // charged back transfers go back to the sender
+                if holder != client {
+                    self.emit(Event::FundsDeposited {
+                        client,
+                        currency,
+                        amount,
+                    });
+                }
+            }
+            SettlementTransaction::Reversal => {
+                let amount = prev.charged_back - deposit.charged_back;
+                if holder != client {
+                    self.emit(Event::FundsWithdrawn {
+                        client,
+                        currency,
+                        amount,
+                    });
+                }
+                self.emit(Event::ChargebackReversed {
+                    client: holder,
+                    currency,
+                    amount,
+                    sender,
+                    complete: deposit.charged_back == Amount::ZERO,
+                });
+            }
+        }
+        let frozen = self
+            .accounts
+            .get(&holder)
+            .is_some_and(|x| matches!(x.account.status, AccountStatus::Frozen));
+        match (was_frozen, frozen) {
+            (false, true) => self.emit(Event::AccountFrozen { client: holder }),
+            (true, false) => self.emit(Event::AccountUnfrozen { client: holder }),
+            _ => {}
+        }
+    }
+
+    /// Flushes every subscriber, returns the first error.
+    pub fn flush_subscribers(&mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        for subscriber in &mut self.subscribers {
+            let flushed = subscriber.flush();
+            if result.is_ok() {
+                result = flushed;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        core::{
+            ledger::Ledger,
+            transaction::{NormalTransaction, SettlementTransaction, Transaction},
+        },
+        Amount, Currency,
+    };
+
+    use super::{Event, JsonLines, LedgerEvent};
+
+    #[test]
+    fn emits_domain_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        let mut ledger = Ledger::default()
+            .with_subscriber(move |event: &LedgerEvent| recorded.lock().unwrap().push(*event));
+        let currency = Currency::default();
+        let amount = Amount::from_num(10);
+        let settle = |tx_id, kind| Transaction::SettlementTransaction {
+            client_id: 1,
+            tx_id,
+            kind,
+            amount: None,
+            timestamp: None,
+        };
+        let deposit = |tx_id| Transaction::Normal {
+            client_id: 1,
+            tx_id,
+            kind: NormalTransaction::Deposit { amount, currency },
+            timestamp: None,
+        };
+        let txs = [
+            deposit(1),
+            deposit(2),
+            Transaction::Normal {
+                client_id: 1,
+                tx_id: 3,
+                kind: NormalTransaction::Withdraw {
+                    amount: Amount::from_num(5),
+                    currency,
+                },
+                timestamp: None,
+            },
+            settle(2, SettlementTransaction::Dispute),
+            settle(2, SettlementTransaction::Resolve),
+            settle(1, SettlementTransaction::Dispute),
+            settle(1, SettlementTransaction::Chargeback),
+        ];
+        for tx in &txs {
+            let _ = ledger.handle_transaction(tx);
+        }
+        let events: Vec<_> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|x| (x.seq, x.event))
+            .collect();
+        let client = 1;
+        assert_eq!(
+            events,
+            vec![
+                (1, Event::AccountOpened { client }),
+                (
+                    1,
+                    Event::FundsDeposited {
+                        client,
+                        currency,
+                        amount
+                    }
+                ),
+                (
+                    2,
+                    Event::FundsDeposited {
+                        client,
+                        currency,
+                        amount
+                    }
+                ),
+                (
+                    3,
+                    Event::FundsWithdrawn {
+                        client,
+                        currency,
+                        amount: Amount::from_num(5)
+                    }
+                ),
+                (
+                    4,
+                    Event::FundsHeld {
+                        client,
+                        currency,
+                        amount,
+                        sender: None
+                    }
+                ),
+                (
+                    5,
+                    Event::FundsReleased {
+                        client,
+                        currency,
+                        amount
+                    }
+                ),
+                (
+                    6,
+                    Event::FundsHeld {
+                        client,
+                        currency,
+                        amount,
+                        sender: None
+                    }
+                ),
+                (
+                    7,
+                    Event::ChargedBack {
+                        client,
+                        currency,
+                        amount,
+                        sender: None,
+                        first: true
+                    }
+                ),
+                (7, Event::AccountFrozen { client }),
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_json_lines() {
+        let out = tempfile::NamedTempFile::new().unwrap();
+        let mut ledger = Ledger::default().with_subscriber(JsonLines::new(out.reopen().unwrap()));
+        ledger
+            .handle_transaction(&Transaction::Normal {
+                client_id: 7,
+                tx_id: 1,
+                kind: NormalTransaction::Deposit {
+                    amount: Amount::from_num(1.5),
+                    currency: "usd".parse().unwrap(),
+                },
+                timestamp: None,
+            })
+            .unwrap();
+        ledger.flush_subscribers().unwrap();
+        expect_test::expect![[r#"
+            {"seq":1,"timestamp":null,"event":"account_opened","client":7}
+            {"seq":1,"timestamp":null,"event":"funds_deposited","client":7,"currency":"USD","amount":"1.5"}
+        "#]]
+        .assert_eq(&std::fs::read_to_string(out.path()).unwrap());
+    }
+
+    #[test]
+    fn amounts_read_back_exactly() {
+        for amount in [
+            Amount::from_num(1.5),
+            // shown as 96.7, which reads back one bit lower
+            Amount::from_bits(6337332),
+            -Amount::from_bits(2988441),
+        ] {
+            let event = LedgerEvent {
+                seq: 1,
+                timestamp: None,
+                event: Event::FeeCharged {
+                    client: 1,
+                    currency: Currency::default(),
+                    amount,
+                },
+            };
+            let json = serde_json::to_string(&event).unwrap();
+            assert_eq!(serde_json::from_str::<LedgerEvent>(&json).unwrap(), event);
+        }
+    }
+}
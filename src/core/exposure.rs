@@ -0,0 +1,173 @@
+use super::{account::AccountStatus, ledger::Ledger};
+use crate::{Amount, ClientId, Currency};
+
+/// A client balance in a single currency that went below zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountExposure {
+    pub client: ClientId,
+    pub currency: Currency,
+    pub available: Amount,
+    pub total: Amount,
+    pub frozen: bool,
+    /// transactions of the client with charged back funds that weren't reversed
+    pub chargebacks: usize,
+    /// net amount charged back from the client in this currency
+    pub charged_back: Amount,
+}
+
+/// Liabilities of the ledger in a single currency, `deposits + chargebacks == liabilities`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiabilityExposure {
+    pub currency: Currency,
+    pub liabilities: Amount,
+    /// deposits still owed to clients net of withdrawals and transfers, negative
+    pub deposits: Amount,
+    /// funds charged back to the ledger net of reversals
+    pub chargebacks: Amount,
+}
+
+impl Ledger {
+    /// Every (client, currency) with a negative available or total balance.
+    pub fn account_exposure(&self) -> impl Iterator<Item = AccountExposure> + '_ {
+        self.accounts.iter().flat_map(|(&client, x)| {
+            x.account
+                .wallets
+                .iter()
+                .filter(|(_, wallet)| {
+                    wallet.available.amount < Amount::ZERO || wallet.total() < Amount::ZERO
+                })
+                .map(move |(&currency, wallet)| AccountExposure {
+                    client,
+                    currency,
+                    available: wallet.available.amount,
+                    total: wallet.total(),
+                    frozen: matches!(x.account.status, AccountStatus::Frozen),
+                    chargebacks: x.chargebacks,
+                    charged_back: x.charged_back.get(&currency).copied().unwrap_or_default(),
+                })
+        })
+    }
+
+    /// Liabilities broken down by cause, one per currency.
+    pub fn liability_exposure(&self) -> impl Iterator<Item = LiabilityExposure> + '_ {
+        self.liabilites.iter().map(|(&currency, balance)| {
+            let chargebacks = self
+                .charged_back
+                .get(&currency)
+                .copied()
+                .unwrap_or_default();
+            LiabilityExposure {
+                currency,
+                liabilities: balance.amount,
+                deposits: balance.amount - chargebacks,
+                chargebacks,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            account::AccountStatus,
+            ledger::Ledger,
+            transaction::{NormalTransaction, SettlementTransaction, Transaction},
+        },
+        Amount, ClientId, Currency, TxId,
+    };
+
+    use super::{AccountExposure, LiabilityExposure};
+
+    fn amount(x: &str) -> Amount {
+        Amount::from_str(x).unwrap()
+    }
+
+    fn normal(client_id: ClientId, tx_id: TxId, kind: NormalTransaction) -> Transaction {
+        Transaction::Normal {
+            client_id,
+            tx_id,
+            kind,
+            timestamp: None,
+        }
+    }
+
+    fn settle(client_id: ClientId, tx_id: TxId, kind: SettlementTransaction) -> Transaction {
+        Transaction::SettlementTransaction {
+            client_id,
+            tx_id,
+            kind,
+            amount: None,
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn negative_accounts_and_liabilities() {
+        let mut ledger = Ledger::default();
+        let currency = Currency::default();
+        let deposit = |value| NormalTransaction::Deposit {
+            amount: amount(value),
+            currency,
+        };
+        let txs = [
+            normal(1, 1, deposit("100")),
+            normal(
+                1,
+                2,
+                NormalTransaction::Withdraw {
+                    amount: amount("70"),
+                    currency,
+                },
+            ),
+            settle(1, 1, SettlementTransaction::Dispute),
+            settle(1, 1, SettlementTransaction::Chargeback),
+            normal(2, 3, deposit("50")),
+            normal(
+                2,
+                4,
+                NormalTransaction::Transfer {
+                    amount: amount("20"),
+                    currency,
+                    to: 3,
+                },
+            ),
+            normal(3, 5, deposit("5")),
+            settle(2, 4, SettlementTransaction::Dispute),
+            settle(2, 4, SettlementTransaction::Chargeback),
+        ];
+        for tx in &txs {
+            // chargebacks report the frozen account
+            let _ = ledger.handle_transaction(tx);
+        }
+        let accounts: Vec<_> = ledger.account_exposure().collect();
+        assert_eq!(
+            accounts,
+            vec![AccountExposure {
+                client: 1,
+                currency,
+                available: amount("-70"),
+                total: amount("-70"),
+                frozen: true,
+                chargebacks: 1,
+                charged_back: amount("100"),
+            }]
+        );
+        // the charged back transfer took client 3 to 5 without going negative
+        assert!(matches!(
+            ledger.get_account(&3).unwrap().status,
+            AccountStatus::Frozen
+        ));
+        let liabilities: Vec<_> = ledger.liability_exposure().collect();
+        assert_eq!(
+            liabilities,
+            vec![LiabilityExposure {
+                currency,
+                liabilities: amount("15"),
+                deposits: amount("-85"),
+                chargebacks: amount("100"),
+            }]
+        );
+        assert!(ledger.verify().unwrap().is_ok());
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+
+use super::{account::Balance, events::Event, interest::round, ledger::Ledger};
+use crate::{Amount, ClientId, Currency};
+
+/// Fee rate on the part of a deposit above `from`, up to the `from` of the next tier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeTier {
+    pub from: Amount,
+    /// rate in basis points, `50` is 0.5%, at most 10000
+    pub bps: u32,
+}
+
+/// Fees charged by the ledger, amounts are the same in every currency.
+/// The default schedule is free.
+#[derive(Debug, Clone, Default)]
+pub struct FeeSchedule {
+    /// charged on top of every applied withdrawal, the client needs funds for both.
+    /// fees and penalties can't be negative
+    pub withdrawal: Amount,
+    /// marginal tiers for deposits, sorted by `from`. the fee is taken from the deposited amount
+    pub deposit_tiers: Vec<FeeTier>,
+    /// charged after a chargeback, may leave the account negative
+    pub chargeback_penalty: Amount,
+}
+
+impl FeeSchedule {
+    pub fn new(
+        withdrawal: Amount,
+        mut deposit_tiers: Vec<FeeTier>,
+        chargeback_penalty: Amount,
+    ) -> Self {
+        deposit_tiers.sort_by_key(|tier| tier.from);
+        Self {
+            withdrawal,
+            deposit_tiers,
+            chargeback_penalty,
+        }
+    }
+
+    /// rounded half to even to 4 decimal places
+    pub(super) fn deposit_fee(&self, amount: Amount) -> Amount {
+        let mut fee = Amount::ZERO;
+        for (i, tier) in self.deposit_tiers.iter().enumerate() {
+            let to = self
+                .deposit_tiers
+                .get(i + 1)
+                .map_or(amount, |next| next.from.min(amount));
+            if to > tier.from {
+                fee += (to - tier.from) * i128::from(tier.bps);
+            }
+        }
+        round(fee, 10_000)
+    }
+}
+
+/// Fees charged to a client in a single currency.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FeeTotals {
+    pub deposits: Amount,
+    pub withdrawals: Amount,
+    pub chargebacks: Amount,
+}
+
+impl FeeTotals {
+    pub fn total(&self) -> Amount {
+        self.deposits + self.withdrawals + self.chargebacks
+    }
+}
+
+impl Ledger {
+    pub fn with_fees(mut self, fees: FeeSchedule) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    /// Moves `fee` from the client's available balance to the house revenue account.
+    pub(super) fn charge_fee(
+        &mut self,
+        client_id: ClientId,
+        currency: Currency,
+        fee: Amount,
+        kind: impl FnOnce(&mut FeeTotals) -> &mut Amount,
+    ) {
+        if fee == Amount::ZERO {
+            return;
+        }
+        let Some(client) = self.accounts.get_mut(&client_id) else {
+            return;
+        };
+        let revenue = self.revenue.entry(currency).or_default();
+        client.account.pay_fee(revenue, currency, fee);
+        *kind(self.fee_totals.entry((client_id, currency)).or_default()) += fee;
+        self.emit(Event::FeeCharged {
+            client: client_id,
+            currency,
+            amount: fee,
+        });
+    }
+
+    /// Fees charged so far per client and currency, ordered by client.
+    pub fn fees(&self) -> &BTreeMap<(ClientId, Currency), FeeTotals> {
+        &self.fee_totals
+    }
+
+    /// Balance of the house revenue account in a single currency, the sum of all fees charged.
+    pub fn revenue(&self, currency: &Currency) -> Amount {
+        self.revenue
+            .get(currency)
+            .map_or(Amount::ZERO, |x: &Balance| x.amount)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            index::TxRecord,
+            ledger::Ledger,
+            transaction::{NormalTransaction, SettlementTransaction, Transaction},
+        },
+        Amount, ClientId, Currency, TxId,
+    };
+
+    use super::{FeeSchedule, FeeTier, FeeTotals};
+
+    fn amount(x: &str) -> Amount {
+        Amount::from_str(x).unwrap()
+    }
+
+    fn normal(client_id: ClientId, tx_id: TxId, kind: NormalTransaction) -> Transaction {
+        Transaction::Normal {
+            client_id,
+            tx_id,
+            kind,
+            timestamp: None,
+        }
+    }
+
+    fn schedule() -> FeeSchedule {
+        FeeSchedule::new(
+            amount("0.5"),
+            vec![
+                FeeTier {
+                    from: amount("1000"),
+                    bps: 100,
+                },
+                FeeTier {
+                    from: amount("100"),
+                    bps: 50,
+                },
+            ],
+            amount("15"),
+        )
+    }
+
+    #[test]
+    fn tiered_deposit_fee() {
+        let fees = schedule();
+        assert_eq!(fees.deposit_fee(amount("100")), Amount::ZERO);
+        assert_eq!(fees.deposit_fee(amount("300")), amount("1"));
+        assert_eq!(fees.deposit_fee(amount("1100")), amount("5.5"));
+        assert_eq!(fees.deposit_fee(amount("100.01")), amount("0"));
+    }
+
+    #[test]
+    fn fees_go_to_revenue() {
+        let mut ledger = Ledger::default().with_fees(schedule());
+        let currency = Currency::default();
+        let deposit = |value| NormalTransaction::Deposit {
+            amount: amount(value),
+            currency,
+        };
+        let withdraw = |value| NormalTransaction::Withdraw {
+            amount: amount(value),
+            currency,
+        };
+        let txs = [
+            normal(1, 1, deposit("300")),
+            normal(1, 2, withdraw("10")),
+            // can't pay the fee on top
+            normal(1, 3, withdraw("289")),
+            normal(2, 4, deposit("50")),
+            Transaction::SettlementTransaction {
+                client_id: 2,
+                tx_id: 4,
+                kind: SettlementTransaction::Dispute,
+                amount: None,
+                timestamp: None,
+            },
+            Transaction::SettlementTransaction {
+                client_id: 2,
+                tx_id: 4,
+                kind: SettlementTransaction::Chargeback,
+                amount: None,
+                timestamp: None,
+            },
+        ];
+        for tx in &txs {
+            let _ = ledger.handle_transaction(tx);
+        }
+        let total = |client| ledger.get_account(&client).unwrap().total(&currency);
+        assert_eq!(total(1), amount("288.5"));
+        assert_eq!(total(2), amount("-15"));
+        assert_eq!(
+            ledger.fees()[&(1, currency)],
+            FeeTotals {
+                deposits: amount("1"),
+                withdrawals: amount("0.5"),
+                chargebacks: Amount::ZERO,
+            }
+        );
+        assert_eq!(ledger.fees()[&(2, currency)].total(), amount("15"));
+        assert_eq!(ledger.revenue(&currency), amount("16.5"));
+        assert!(ledger.verify().unwrap().is_ok());
+
+        let history = ledger.history(1).unwrap();
+        let fees: Vec<_> = history.iter().map(|(_, record)| record.fee()).collect();
+        assert_eq!(fees, vec![amount("1"), amount("0.5"), Amount::ZERO]);
+        assert!(matches!(history[2].1, TxRecord::Withdrawal { .. }));
+    }
+}
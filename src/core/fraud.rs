@@ -0,0 +1,308 @@
+use chrono::TimeDelta;
+use tracing::debug;
+
+use super::{
+    account::Account,
+    ledger::{Client, Ledger},
+    limits::Rejection,
+    transaction::{NormalTransaction, SettlementTransaction, Transaction},
+};
+use crate::{Amount, Currency, Timestamp};
+
+/// Result of a [`FraudCheck`], scores of every check are added up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Allow { score: u32 },
+    Reject { score: u32, reason: String },
+}
+
+impl Verdict {
+    pub fn score(&self) -> u32 {
+        match self {
+            Verdict::Allow { score } | Verdict::Reject { score, .. } => *score,
+        }
+    }
+}
+
+/// Read-only state of the client a transaction belongs to.
+#[derive(Clone, Copy)]
+pub struct ClientView<'a>(&'a Client);
+
+impl<'a> ClientView<'a> {
+    pub fn account(&self) -> &'a Account {
+        &self.0.account
+    }
+    pub fn chargebacks(&self) -> usize {
+        self.0.chargebacks
+    }
+    /// disputes opened by the client so far
+    pub fn disputes(&self) -> usize {
+        self.0.disputes
+    }
+    pub fn last_deposit(&self) -> Option<&'a RecentDeposit> {
+        self.0.last_deposit.as_ref()
+    }
+}
+
+/// The latest deposit applied to a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecentDeposit {
+    pub amount: Amount,
+    pub currency: Currency,
+    pub timestamp: Option<Timestamp>,
+}
+
+/// Rule evaluated before the ledger applies a client transaction.
+/// `client` is `None` for clients the ledger hasn't seen yet.
+pub trait FraudCheck: Send {
+    fn check(&self, tx: &Transaction, client: Option<ClientView<'_>>) -> Verdict;
+}
+
+/// Checks run by the ledger, the default one has none and scores everything 0.
+#[derive(Default)]
+pub struct FraudPolicy {
+    pub checks: Vec<Box<dyn FraudCheck>>,
+    /// rejects transactions whose total score reaches this even if every check allowed them
+    pub reject_score: Option<u32>,
+    /// client transactions scoring at least this (and not rejected) wait for review,
+    /// see [`super::review`]
+    pub review_score: Option<u32>,
+}
+
+/// Scores disputes by the number of disputes the client opened before,
+/// `points` each, and rejects them once there were `max` of them.
+pub struct TooManyDisputes {
+    pub max: usize,
+    pub points: u32,
+}
+
+impl FraudCheck for TooManyDisputes {
+    fn check(&self, tx: &Transaction, client: Option<ClientView<'_>>) -> Verdict {
+        let Transaction::SettlementTransaction {
+            kind: SettlementTransaction::Dispute,
+            ..
+        } = tx
+        else {
+            return Verdict::Allow { score: 0 };
+        };
+        let disputes = client.map_or(0, |x| x.disputes());
+        let score = self.points.saturating_mul(disputes as u32);
+        if disputes >= self.max {
+            return Verdict::Reject {
+                score,
+                reason: format!("client already opened {disputes} disputes"),
+            };
+        }
+        Verdict::Allow { score }
+    }
+}
+
+/// Scores withdrawals and outgoing transfers made within `window` of a deposit of at least
+/// `min_deposit` in the same currency. Needs timestamps, the window never matches without them.
+pub struct WithdrawalAfterDeposit {
+    pub min_deposit: Amount,
+    pub window: TimeDelta,
+    pub score: u32,
+}
+
+impl FraudCheck for WithdrawalAfterDeposit {
+    fn check(&self, tx: &Transaction, client: Option<ClientView<'_>>) -> Verdict {
+        let Transaction::Normal {
+            kind:
+                NormalTransaction::Withdraw { currency, .. }
+                | NormalTransaction::Transfer { currency, .. },
+            timestamp: Some(now),
+            ..
+        } = tx
+        else {
+            return Verdict::Allow { score: 0 };
+        };
+        let recent = client
+            .and_then(|x| x.last_deposit())
+            .is_some_and(|deposit| {
+                deposit.currency == *currency
+                    && deposit.amount >= self.min_deposit
+                    && deposit.timestamp.is_some_and(|at| *now - at <= self.window)
+            });
+        Verdict::Allow {
+            score: if recent { self.score } else { 0 },
+        }
+    }
+}
+
+impl Ledger {
+    pub fn with_fraud(mut self, fraud: FraudPolicy) -> Self {
+        self.fraud = fraud;
+        self
+    }
+
+    /// Runs every check against `tx`, returns the total score or why it was rejected.
+    pub(super) fn screen(&self, tx: &Transaction) -> Result<u32, Rejection> {
+        if self.fraud.checks.is_empty() {
+            return Ok(0);
+        }
+        let client = tx
+            .client_id()
+            .and_then(|id| self.accounts.get(&id))
+            .map(ClientView);
+        let mut score = 0u32;
+        let mut rejected = None;
+        for check in &self.fraud.checks {
+            let verdict = check.check(tx, client);
+            debug!(?verdict, "fraud check");
+            score = score.saturating_add(verdict.score());
+            if let Verdict::Reject { reason, .. } = verdict {
+                rejected.get_or_insert(reason);
+            }
+        }
+        if let Some(reason) = rejected {
+            return Err(Rejection::Fraud { score, reason });
+        }
+        if let Some(limit) = self.fraud.reject_score.filter(|limit| score >= *limit) {
+            return Err(Rejection::Fraud {
+                score,
+                reason: format!("score reached {limit}"),
+            });
+        }
+        Ok(score)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeDelta;
+
+    use crate::{
+        core::{
+            index::TxRecord,
+            ledger::{Errors, Ledger},
+            limits::Rejection,
+            transaction::{NormalTransaction, SettlementTransaction, Transaction},
+        },
+        Amount, ClientId, Currency, Timestamp, TxId,
+    };
+
+    use super::{FraudPolicy, TooManyDisputes, WithdrawalAfterDeposit};
+
+    fn amount(x: &str) -> Amount {
+        Amount::from_str(x).unwrap()
+    }
+
+    fn normal(client_id: ClientId, tx_id: TxId, kind: NormalTransaction, hour: i64) -> Transaction {
+        Transaction::Normal {
+            client_id,
+            tx_id,
+            kind,
+            timestamp: Some(
+                "2024-01-01T00:00:00Z".parse::<Timestamp>().unwrap() + TimeDelta::hours(hour),
+            ),
+        }
+    }
+
+    fn dispute(client_id: ClientId, tx_id: TxId) -> Transaction {
+        Transaction::SettlementTransaction {
+            client_id,
+            tx_id,
+            kind: SettlementTransaction::Dispute,
+            amount: None,
+            timestamp: None,
+        }
+    }
+
+    fn ledger() -> Ledger {
+        Ledger::default().with_fraud(FraudPolicy {
+            checks: vec![
+                Box::new(TooManyDisputes { max: 2, points: 10 }),
+                Box::new(WithdrawalAfterDeposit {
+                    min_deposit: amount("1000"),
+                    window: TimeDelta::hours(1),
+                    score: 60,
+                }),
+            ],
+            reject_score: Some(100),
+            review_score: None,
+        })
+    }
+
+    #[test]
+    fn scores_and_rejects() {
+        let mut ledger = ledger();
+        let currency = Currency::default();
+        let deposit = |value| NormalTransaction::Deposit {
+            amount: amount(value),
+            currency,
+        };
+        let withdraw = |value| NormalTransaction::Withdraw {
+            amount: amount(value),
+            currency,
+        };
+        for (tx_id, hour) in [(1, 0), (2, 0), (3, 0)] {
+            ledger
+                .handle_transaction(&normal(1, tx_id, deposit("1"), hour))
+                .unwrap();
+        }
+        ledger.handle_transaction(&dispute(1, 1)).unwrap();
+        ledger.handle_transaction(&dispute(1, 2)).unwrap();
+        let Err(Errors::Rejected(Rejection::Fraud { score, .. })) =
+            ledger.handle_transaction(&dispute(1, 3))
+        else {
+            panic!("third dispute should be rejected");
+        };
+        assert_eq!(score, 20);
+
+        ledger
+            .handle_transaction(&normal(2, 4, deposit("5000"), 0))
+            .unwrap();
+        ledger
+            .handle_transaction(&normal(2, 5, withdraw("10"), 1))
+            .unwrap();
+        ledger
+            .handle_transaction(&normal(2, 6, withdraw("10"), 2))
+            .unwrap();
+        let scores: Vec<_> = ledger
+            .history(2)
+            .unwrap()
+            .iter()
+            .map(|(_, record)| record.score())
+            .collect();
+        assert_eq!(scores, vec![0, 60, 0]);
+        assert!(matches!(
+            ledger.history(2).unwrap()[1].1,
+            TxRecord::Withdrawal { .. }
+        ));
+        assert!(ledger.verify().unwrap().is_ok());
+    }
+
+    #[test]
+    fn combined_score_rejects() {
+        let mut ledger = ledger();
+        ledger.fraud.reject_score = Some(50);
+        let currency = Currency::default();
+        ledger
+            .handle_transaction(&normal(
+                1,
+                1,
+                NormalTransaction::Deposit {
+                    amount: amount("1000"),
+                    currency,
+                },
+                0,
+            ))
+            .unwrap();
+        let result = ledger.handle_transaction(&normal(
+            1,
+            2,
+            NormalTransaction::Withdraw {
+                amount: amount("1000"),
+                currency,
+            },
+            0,
+        ));
+        assert!(matches!(
+            result,
+            Err(Errors::Rejected(Rejection::Fraud { score: 60, .. }))
+        ));
+        let total = ledger.get_account(&1).unwrap().total(&currency);
+        assert_eq!(total, amount("1000"));
+    }
+}
@@ -0,0 +1,344 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+
+use super::{bury_in, Tombstone, TxIndex, TxKey, TxRecord, TxSpace};
+use crate::{ClientId, TxId};
+
+/// every page holds records for 4096 consecutive tx ids (of all clients)
+const PAGE_BITS: u32 = 12;
+
+#[derive(Default, Serialize, Deserialize)]
+struct Page {
+    records: BTreeMap<TxKey, TxRecord>,
+    tombstones: BTreeMap<TxKey, Tombstone>,
+}
+
+struct CachedPage {
+    page: Page,
+    dirty: bool,
+}
+
+/// Index stored as page files in a local directory with an LRU cache of pages in front of it.
+/// Pages group consecutive tx ids, so recent activity (the usual target of disputes) stays
+/// in memory while old history only costs disk space.
+/// The cache holds at most `cache_pages` pages. A page holds every record and tombstone whose tx
+/// id falls in its range of 4096 ids, of all clients, so its size grows with the number of
+/// clients reusing the same ids.
+pub struct DiskIndex {
+    dir: PathBuf,
+    cache: LruCache<u32, CachedPage>,
+    stored: BTreeSet<u32>,
+}
+
+fn page_id(tx_id: TxId) -> u32 {
+    tx_id >> PAGE_BITS
+}
+
+fn to_io(err: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+impl DiskIndex {
+    /// `dir` is created if missing and must not contain pages from a previous run,
+    /// see [`DiskIndex::clear`].
+    pub fn new<P: AsRef<Path>>(dir: P, cache_pages: NonZeroUsize) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        for entry in fs::read_dir(&dir)? {
+            if entry?.path().extension().is_some_and(|ext| ext == "page") {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!(
+                        "index directory {} already contains pages, clear it to reuse it",
+                        dir.display()
+                    ),
+                ));
+            }
+        }
+        Ok(Self {
+            dir,
+            cache: LruCache::new(cache_pages),
+            stored: BTreeSet::new(),
+        })
+    }
+
+    /// Removes the pages left in `dir` by a previous run, other files are kept.
+    pub fn clear<P: AsRef<Path>>(dir: P) -> io::Result<()> {
+        let dir = dir.as_ref();
+        if !dir.exists() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "page") {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, page_id: u32) -> PathBuf {
+        self.dir.join(format!("{page_id:08x}.page"))
+    }
+
+    fn read(&self, page_id: u32) -> io::Result<Page> {
+        if !self.stored.contains(&page_id) {
+            return Ok(Page::default());
+        }
+        let file = BufReader::new(File::open(self.path(page_id))?);
+        bincode::deserialize_from(file).map_err(to_io)
+    }
+
+    fn write(&self, page_id: u32, page: &Page) -> io::Result<()> {
+        let file = BufWriter::new(File::create(self.path(page_id))?);
+        bincode::serialize_into(file, page).map_err(to_io)
+    }
+
+    fn page_ids(&self) -> BTreeSet<u32> {
+        let cached = self.cache.iter().map(|(id, _)| *id);
+        self.stored.iter().copied().chain(cached).collect()
+    }
+
+    fn page(&mut self, page_id: u32) -> io::Result<&mut CachedPage> {
+        if !self.cache.contains(&page_id) {
+            let page = self.read(page_id)?;
+            // the page making room is written before it leaves the cache,
+            // if that fails it stays cached and nothing is lost
+            if self.cache.len() == self.cache.cap().get() {
+                if let Some((&evicted_id, evicted)) = self.cache.peek_lru() {
+                    if evicted.dirty {
+                        self.write(evicted_id, &evicted.page)?;
+                        self.stored.insert(evicted_id);
+                    }
+                }
+            }
+            let loaded = CachedPage { page, dirty: false };
+            self.cache.push(page_id, loaded);
+        }
+        Ok(self.cache.get_mut(&page_id).expect("page was just cached"))
+    }
+}
+
+impl TxIndex for DiskIndex {
+    fn get(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        space: TxSpace,
+    ) -> io::Result<Option<TxRecord>> {
+        let cached = self.page(page_id(tx_id))?;
+        Ok(cached.page.records.get(&(client_id, tx_id, space)).cloned())
+    }
+
+    fn insert(&mut self, client_id: ClientId, tx_id: TxId, record: TxRecord) -> io::Result<()> {
+        let cached = self.page(page_id(tx_id))?;
+        let key = (client_id, tx_id, record.space());
+        cached.page.records.insert(key, record);
+        cached.dirty = true;
+        Ok(())
+    }
+
+    fn tombstone(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        space: TxSpace,
+    ) -> io::Result<Option<Tombstone>> {
+        let cached = self.page(page_id(tx_id))?;
+        Ok(cached
+            .page
+            .tombstones
+            .get(&(client_id, tx_id, space))
+            .copied())
+    }
+
+    fn for_each(&self, f: &mut dyn FnMut(ClientId, TxId, &TxRecord)) -> io::Result<()> {
+        for page_id in self.page_ids() {
+            let stored;
+            let page = match self.cache.peek(&page_id) {
+                Some(cached) => &cached.page,
+                None => {
+                    stored = self.read(page_id)?;
+                    &stored
+                }
+            };
+            for (&(client_id, tx_id, _), record) in &page.records {
+                f(client_id, tx_id, record)
+            }
+        }
+        Ok(())
+    }
+
+    fn bury(
+        &mut self,
+        bury: &mut dyn FnMut(ClientId, TxId, &TxRecord) -> Option<Tombstone>,
+    ) -> io::Result<usize> {
+        let mut buried = 0;
+        for page_id in self.page_ids() {
+            // pages that aren't cached are rewritten in place, sweeping shouldn't thrash the cache
+            match self.cache.peek_mut(&page_id) {
+                Some(cached) => {
+                    let page = &mut cached.page;
+                    let removed = bury_in(&mut page.records, &mut page.tombstones, bury);
+                    cached.dirty |= removed > 0;
+                    buried += removed;
+                }
+                None => {
+                    let mut page = self.read(page_id)?;
+                    let removed = bury_in(&mut page.records, &mut page.tombstones, bury);
+                    if removed > 0 {
+                        self.write(page_id, &page)?;
+                    }
+                    buried += removed;
+                }
+            }
+        }
+        Ok(buried)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroUsize;
+
+    use crate::{
+        core::{
+            index::{MemoryIndex, Tombstone, TxIndex, TxRecord, TxSpace},
+            ledger::Ledger,
+            transaction::Transaction,
+        },
+        Amount, CsvAccount, Currency, Workload, WorkloadConfig, WorkloadRow,
+    };
+
+    use super::DiskIndex;
+
+    #[test]
+    fn evicted_pages_are_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut index = DiskIndex::new(dir.path(), NonZeroUsize::new(1).unwrap()).unwrap();
+        let amount = |x: u32| TxRecord::Withdrawal {
+            amount: Amount::from_num(x),
+            seq: x.into(),
+            timestamp: None,
+            currency: Currency::default(),
+            fee: Amount::ZERO,
+            score: 0,
+        };
+        for tx_id in (0..50_000).step_by(1000) {
+            index.insert(1, tx_id, amount(tx_id)).unwrap();
+        }
+        index.insert(2, 0, amount(7)).unwrap();
+
+        assert_eq!(
+            index.get(1, 0, TxSpace::Withdrawal).unwrap(),
+            Some(amount(0))
+        );
+        assert_eq!(
+            index.get(2, 0, TxSpace::Withdrawal).unwrap(),
+            Some(amount(7))
+        );
+        assert_eq!(
+            index.get(1, 49_000, TxSpace::Withdrawal).unwrap(),
+            Some(amount(49_000))
+        );
+        assert_eq!(index.get(1, 49_001, TxSpace::Withdrawal).unwrap(), None);
+        let mut visited = 0;
+        index.for_each(&mut |_, _, _| visited += 1).unwrap();
+        assert_eq!(visited, 51);
+
+        let buried = index
+            .bury(&mut |_, tx_id, _| (tx_id < 25_000).then_some(Tombstone::Expired))
+            .unwrap();
+        assert_eq!(buried, 26);
+        assert_eq!(index.get(1, 0, TxSpace::Withdrawal).unwrap(), None);
+        // tombstones are paged out with the records
+        assert_eq!(
+            index.tombstone(2, 0, TxSpace::Withdrawal).unwrap(),
+            Some(Tombstone::Expired)
+        );
+        assert_eq!(index.tombstone(1, 0, TxSpace::Disputable).unwrap(), None);
+        assert_eq!(
+            index.tombstone(1, 49_000, TxSpace::Withdrawal).unwrap(),
+            None
+        );
+        assert_eq!(
+            index.get(1, 49_000, TxSpace::Withdrawal).unwrap(),
+            Some(amount(49_000))
+        );
+    }
+
+    #[test]
+    fn refuses_dirty_directory_until_cleared() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut index = DiskIndex::new(dir.path(), NonZeroUsize::new(1).unwrap()).unwrap();
+        index
+            .insert(
+                1,
+                0,
+                TxRecord::Withdrawal {
+                    amount: Amount::ONE,
+                    seq: 1,
+                    timestamp: None,
+                    currency: Currency::default(),
+                    fee: Amount::ZERO,
+                    score: 0,
+                },
+            )
+            .unwrap();
+        index
+            .insert(
+                1,
+                1 << 20,
+                TxRecord::Withdrawal {
+                    amount: Amount::ONE,
+                    seq: 1,
+                    timestamp: None,
+                    currency: Currency::default(),
+                    fee: Amount::ZERO,
+                    score: 0,
+                },
+            )
+            .unwrap();
+        assert!(DiskIndex::new(dir.path(), NonZeroUsize::new(1).unwrap()).is_err());
+        DiskIndex::clear(dir.path()).unwrap();
+        let mut index = DiskIndex::new(dir.path(), NonZeroUsize::new(1).unwrap()).unwrap();
+        assert_eq!(index.get(1, 0, TxSpace::Withdrawal).unwrap(), None);
+    }
+
+    #[test]
+    fn same_outcome_as_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = DiskIndex::new(dir.path(), NonZeroUsize::new(2).unwrap()).unwrap();
+        let mut on_disk = Ledger::with_index(disk);
+        let mut in_memory = Ledger::with_index(MemoryIndex::default());
+        let workload = Workload::new(WorkloadConfig {
+            clients: 50,
+            transactions: 50_000,
+            dispute_rate: 0.1,
+            settle_rate: 0.1,
+            ..Default::default()
+        });
+        for row in workload {
+            let WorkloadRow::Transaction(tx) = row else {
+                continue;
+            };
+            let Ok(tx) = Transaction::try_from(tx) else {
+                continue;
+            };
+            let _ = on_disk.handle_transaction(&tx);
+            let _ = in_memory.handle_transaction(&tx);
+        }
+        let on_disk_accounts: Vec<CsvAccount> = on_disk.rows().collect();
+        let in_memory_accounts: Vec<CsvAccount> = in_memory.rows().collect();
+        assert_eq!(on_disk_accounts, in_memory_accounts);
+        assert!(on_disk.verify().unwrap().is_ok());
+    }
+}
@@ -0,0 +1,271 @@
+use std::{collections::BTreeMap, io};
+
+use serde::{Deserialize, Serialize};
+
+use super::{ledger::DepositOutcome, transaction::NormalTransaction};
+use crate::{
+    csv::history::{HistoryKind, HistoryRecord},
+    Amount, ClientId, Currency, Timestamp, TxId,
+};
+
+pub mod disk;
+
+/// Tx id spaces of a client: deposits and transfers (both disputable) share one, withdrawals
+/// have their own, so a deposit and a withdrawal may use the same tx id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum TxSpace {
+    Disputable,
+    Withdrawal,
+}
+
+impl From<&NormalTransaction> for TxSpace {
+    fn from(tx: &NormalTransaction) -> Self {
+        match tx {
+            NormalTransaction::Deposit { .. } | NormalTransaction::Transfer { .. } => {
+                TxSpace::Disputable
+            }
+            NormalTransaction::Withdraw { .. } => TxSpace::Withdrawal,
+        }
+    }
+}
+
+type TxKey = (ClientId, TxId, TxSpace);
+
+/// Left in the index in place of a record dropped by [`super::ledger::Ledger::evict`],
+/// keeps the tx id taken and remembers why the record went away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tombstone {
+    /// past the dispute window
+    Expired,
+    /// resolved or charged back, see [`super::retention::RetentionPolicy::evict_settled`]
+    Settled,
+}
+
+/// Everything the ledger remembers about an applied deposit or withdrawal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxRecord {
+    Deposit(DepositOutcome),
+    Withdrawal {
+        amount: Amount,
+        currency: Currency,
+        /// zero if the withdrawal wasn't applied
+        fee: Amount,
+        /// see [`super::fraud::FraudPolicy`]
+        score: u32,
+        seq: u64,
+        timestamp: Option<Timestamp>,
+    },
+    /// indexed under the sender, disputes hold the funds at `to`
+    Transfer {
+        to: ClientId,
+        outcome: DepositOutcome,
+    },
+}
+
+impl TxRecord {
+    pub fn space(&self) -> TxSpace {
+        match self {
+            TxRecord::Deposit(_) | TxRecord::Transfer { .. } => TxSpace::Disputable,
+            TxRecord::Withdrawal { .. } => TxSpace::Withdrawal,
+        }
+    }
+    /// position of the transaction in the ledger's input, see [`super::ledger::Ledger::sequence`]
+    pub fn seq(&self) -> u64 {
+        match self {
+            TxRecord::Deposit(deposit)
+            | TxRecord::Transfer {
+                outcome: deposit, ..
+            } => deposit.seq,
+            TxRecord::Withdrawal { seq, .. } => *seq,
+        }
+    }
+    pub fn amount(&self) -> Amount {
+        match self {
+            TxRecord::Deposit(deposit)
+            | TxRecord::Transfer {
+                outcome: deposit, ..
+            } => deposit.amount,
+            TxRecord::Withdrawal { amount, .. } => *amount,
+        }
+    }
+    pub fn currency(&self) -> Currency {
+        match self {
+            TxRecord::Deposit(deposit)
+            | TxRecord::Transfer {
+                outcome: deposit, ..
+            } => deposit.currency,
+            TxRecord::Withdrawal { currency, .. } => *currency,
+        }
+    }
+    /// fees charged for the record, see [`super::fees::FeeSchedule`]
+    pub fn fee(&self) -> Amount {
+        match self {
+            TxRecord::Deposit(deposit)
+            | TxRecord::Transfer {
+                outcome: deposit, ..
+            } => deposit.fee,
+            TxRecord::Withdrawal { fee, .. } => *fee,
+        }
+    }
+    /// total fraud score when the record was applied, see [`super::fraud::FraudPolicy`]
+    pub fn score(&self) -> u32 {
+        match self {
+            TxRecord::Deposit(deposit)
+            | TxRecord::Transfer {
+                outcome: deposit, ..
+            } => deposit.score,
+            TxRecord::Withdrawal { score, .. } => *score,
+        }
+    }
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        match self {
+            TxRecord::Deposit(deposit)
+            | TxRecord::Transfer {
+                outcome: deposit, ..
+            } => deposit.timestamp,
+            TxRecord::Withdrawal { timestamp, .. } => *timestamp,
+        }
+    }
+}
+
+impl From<(ClientId, TxId, &TxRecord)> for HistoryRecord {
+    fn from((client, tx, record): (ClientId, TxId, &TxRecord)) -> Self {
+        let (kind, state, to_client) = match record {
+            TxRecord::Deposit(deposit) => (HistoryKind::Deposit, Some(deposit.kind), None),
+            TxRecord::Withdrawal { .. } => (HistoryKind::Withdrawal, None, None),
+            TxRecord::Transfer { to, outcome } => {
+                (HistoryKind::Transfer, Some(outcome.kind), Some(*to))
+            }
+        };
+        HistoryRecord {
+            client,
+            tx,
+            kind,
+            amount: record.amount(),
+            currency: record.currency(),
+            fee: record.fee(),
+            state,
+            timestamp: record.timestamp(),
+            to_client,
+            score: record.score(),
+        }
+    }
+}
+
+/// Storage for the per-client transaction index, lookups are keyed by `(client, tx, space)`
+/// so the same tx id can be reused by different clients, see [`TxSpace`].
+pub trait TxIndex: Send {
+    fn get(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        space: TxSpace,
+    ) -> io::Result<Option<TxRecord>>;
+    /// inserts a new record or overwrites the existing one in the record's [`TxSpace`]
+    fn insert(&mut self, client_id: ClientId, tx_id: TxId, record: TxRecord) -> io::Result<()>;
+    /// tombstone left by [`TxIndex::bury`], `None` for ids that were never buried
+    fn tombstone(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        space: TxSpace,
+    ) -> io::Result<Option<Tombstone>>;
+    /// visits every stored record, order is up to the implementation
+    fn for_each(&self, f: &mut dyn FnMut(ClientId, TxId, &TxRecord)) -> io::Result<()>;
+    /// visits every stored record of a single client, order is up to the implementation
+    fn for_client(
+        &self,
+        client_id: ClientId,
+        f: &mut dyn FnMut(TxId, &TxRecord),
+    ) -> io::Result<()> {
+        self.for_each(&mut |client, tx_id, record| {
+            if client == client_id {
+                f(tx_id, record)
+            }
+        })
+    }
+    /// replaces every record `bury` returns a tombstone for with that tombstone, returns the
+    /// number of replaced records
+    fn bury(
+        &mut self,
+        bury: &mut dyn FnMut(ClientId, TxId, &TxRecord) -> Option<Tombstone>,
+    ) -> io::Result<usize>;
+}
+
+/// Keeps the whole index in memory, grows without bounds.
+#[derive(Default)]
+pub struct MemoryIndex {
+    records: BTreeMap<TxKey, TxRecord>,
+    tombstones: BTreeMap<TxKey, Tombstone>,
+}
+
+impl TxIndex for MemoryIndex {
+    fn get(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        space: TxSpace,
+    ) -> io::Result<Option<TxRecord>> {
+        Ok(self.records.get(&(client_id, tx_id, space)).cloned())
+    }
+
+    fn insert(&mut self, client_id: ClientId, tx_id: TxId, record: TxRecord) -> io::Result<()> {
+        self.records
+            .insert((client_id, tx_id, record.space()), record);
+        Ok(())
+    }
+
+    fn tombstone(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        space: TxSpace,
+    ) -> io::Result<Option<Tombstone>> {
+        Ok(self.tombstones.get(&(client_id, tx_id, space)).copied())
+    }
+
+    fn for_each(&self, f: &mut dyn FnMut(ClientId, TxId, &TxRecord)) -> io::Result<()> {
+        for (&(client_id, tx_id, _), record) in &self.records {
+            f(client_id, tx_id, record)
+        }
+        Ok(())
+    }
+
+    fn for_client(
+        &self,
+        client_id: ClientId,
+        f: &mut dyn FnMut(TxId, &TxRecord),
+    ) -> io::Result<()> {
+        let range =
+            (client_id, 0, TxSpace::Disputable)..=(client_id, TxId::MAX, TxSpace::Withdrawal);
+        for (&(_, tx_id, _), record) in self.records.range(range) {
+            f(tx_id, record)
+        }
+        Ok(())
+    }
+
+    fn bury(
+        &mut self,
+        bury: &mut dyn FnMut(ClientId, TxId, &TxRecord) -> Option<Tombstone>,
+    ) -> io::Result<usize> {
+        Ok(bury_in(&mut self.records, &mut self.tombstones, bury))
+    }
+}
+
+/// moves the records `bury` returns a tombstone for from `records` to `tombstones`
+fn bury_in(
+    records: &mut BTreeMap<TxKey, TxRecord>,
+    tombstones: &mut BTreeMap<TxKey, Tombstone>,
+    bury: &mut dyn FnMut(ClientId, TxId, &TxRecord) -> Option<Tombstone>,
+) -> usize {
+    let before = records.len();
+    records.retain(|&key, record| {
+        let (client_id, tx_id, _) = key;
+        let tombstone = bury(client_id, tx_id, record);
+        if let Some(tombstone) = tombstone {
+            tombstones.insert(key, tombstone);
+        }
+        tombstone.is_none()
+    });
+    before - records.len()
+}
@@ -0,0 +1,304 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use tracing::debug;
+
+use super::{account::Balance, events::Event, ledger::Ledger};
+use crate::{Amount, ClientId, Currency};
+
+/// basis points in 100%
+const BPS: i128 = 10_000;
+/// posted interest is rounded to this many decimal places
+const SCALE: i128 = 10_000;
+
+/// Annual rate paid on the part of a positive balance above `from`,
+/// up to the `from` of the next tier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateTier {
+    pub from: Amount,
+    /// annual rate in basis points, `150` is 1.5%
+    pub bps: u32,
+}
+
+/// Interest on `available` balances, accrued daily and posted on demand.
+/// The default policy doesn't pay or charge anything.
+#[derive(Debug, Clone)]
+pub struct InterestPolicy {
+    /// marginal tiers for positive balances, sorted by `from`
+    pub tiers: Vec<RateTier>,
+    /// annual rate in basis points charged on negative balances
+    pub debit_bps: u32,
+    /// days in a year used to turn annual rates into daily ones, usually 365 or 360.
+    /// zero is treated as one
+    pub day_count: u32,
+}
+
+impl Default for InterestPolicy {
+    fn default() -> Self {
+        Self::new(Vec::new(), 0)
+    }
+}
+
+impl InterestPolicy {
+    pub fn new(mut tiers: Vec<RateTier>, debit_bps: u32) -> Self {
+        tiers.sort_by_key(|tier| tier.from);
+        Self {
+            tiers,
+            debit_bps,
+            day_count: 365,
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.debit_bps > 0 || self.tiers.iter().any(|tier| tier.bps > 0)
+    }
+
+    /// one day of interest on `balance` multiplied by `BPS * day_count`, so it stays exact
+    fn daily(&self, balance: Amount) -> Amount {
+        if balance < Amount::ZERO {
+            return balance * i128::from(self.debit_bps);
+        }
+        let mut total = Amount::ZERO;
+        for (i, tier) in self.tiers.iter().enumerate() {
+            let to = self
+                .tiers
+                .get(i + 1)
+                .map_or(balance, |next| next.from.min(balance));
+            if to > tier.from {
+                total += (to - tier.from) * i128::from(tier.bps);
+            }
+        }
+        total
+    }
+
+    fn denominator(&self) -> i128 {
+        BPS * i128::from(self.day_count.max(1))
+    }
+}
+
+/// Accrued interest that wasn't posted yet.
+#[derive(Debug, Default)]
+pub(super) struct Accruals {
+    /// last day interest was accrued for, `None` until the first timestamp
+    through: Option<NaiveDate>,
+    /// per client and currency, in units of `InterestPolicy::denominator`
+    pending: BTreeMap<(ClientId, Currency), Amount>,
+}
+
+/// Interest moved between an account and the house interest balance by [`Ledger::post_interest`].
+/// Positive amounts were paid to the client, negative ones charged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterestPosting {
+    pub client: ClientId,
+    pub currency: Currency,
+    pub amount: Amount,
+}
+
+fn div_round_half_even(n: i128, d: i128) -> i128 {
+    let (q, r) = (n.div_euclid(d), n.rem_euclid(d));
+    match (2 * r).cmp(&d) {
+        std::cmp::Ordering::Greater => q + 1,
+        std::cmp::Ordering::Equal if q % 2 != 0 => q + 1,
+        _ => q,
+    }
+}
+
+/// `pending / denominator` rounded half to even to [`SCALE`] decimal places
+pub(super) fn round(pending: Amount, denominator: i128) -> Amount {
+    let fraction = 1 << Amount::FRAC_NBITS;
+    let scaled = div_round_half_even(pending.to_bits() * SCALE, denominator * fraction);
+    Amount::from_bits(div_round_half_even(scaled * fraction, SCALE))
+}
+
+impl Ledger {
+    pub fn with_interest(mut self, interest: InterestPolicy) -> Self {
+        self.interest = interest;
+        self
+    }
+
+    /// Accrues interest for every day between the last accrual and `date`,
+    /// balances are taken as they are when called (the close of the previous day).
+    pub(super) fn accrue_until(&mut self, date: NaiveDate) {
+        if !self.interest.is_enabled() {
+            return;
+        }
+        let through = *self.accruals.through.get_or_insert(date);
+        let days = (date - through).num_days();
+        if days > 0 {
+            self.accrue_interest(days as u32);
+            self.accruals.through = Some(date);
+        }
+    }
+
+    /// Accrues `days` days of interest on the current balances. Called automatically when
+    /// transaction timestamps cross a day boundary, inputs without timestamps have to call it.
+    pub fn accrue_interest(&mut self, days: u32) {
+        for (&client, entry) in &self.accounts {
+            for (&currency, wallet) in &entry.account.wallets {
+                let daily = self.interest.daily(wallet.available.amount);
+                if daily != Amount::ZERO {
+                    *self.accruals.pending.entry((client, currency)).or_default() +=
+                        daily * i128::from(days);
+                }
+            }
+        }
+    }
+
+    /// Posts accrued interest against the house interest balance of each currency.
+    /// Amounts are rounded half to even to 4 decimal places, the rounding remainder
+    /// stays accrued for the next posting so nothing is lost over time.
+    pub fn post_interest(&mut self) -> Vec<InterestPosting> {
+        let denominator = self.interest.denominator();
+        let mut postings = Vec::new();
+        for (&(client, currency), pending) in self.accruals.pending.iter_mut() {
+            let amount = round(*pending, denominator);
+            if amount == Amount::ZERO {
+                continue;
+            }
+            let Some(entry) = self.accounts.get_mut(&client) else {
+                continue;
+            };
+            let house = self.interest_house.entry(currency).or_default();
+            debug!(client, %currency, %amount, "posting interest");
+            entry.account.post_interest(house, currency, amount);
+            *pending -= amount * denominator;
+            postings.push(InterestPosting {
+                client,
+                currency,
+                amount,
+            });
+        }
+        self.accruals
+            .pending
+            .retain(|_, pending| *pending != Amount::ZERO);
+        for posting in &postings {
+            self.emit(Event::InterestPosted {
+                client: posting.client,
+                currency: posting.currency,
+                amount: posting.amount,
+            });
+        }
+        postings
+    }
+
+    /// Balance of the house interest account in a single currency, the negated sum of all
+    /// interest posted so far.
+    pub fn interest_house(&self, currency: &Currency) -> Amount {
+        self.interest_house
+            .get(currency)
+            .map_or(Amount::ZERO, |x: &Balance| x.amount)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            ledger::Ledger,
+            transaction::{ControlTransaction, NormalTransaction, Transaction},
+        },
+        Amount, ClientId, Currency, TxId,
+    };
+
+    use super::{round, InterestPolicy, RateTier};
+
+    fn amount(x: &str) -> Amount {
+        Amount::from_str(x).unwrap()
+    }
+
+    fn deposit(client_id: ClientId, tx_id: TxId, value: &str, day: u32) -> Transaction {
+        Transaction::Normal {
+            client_id,
+            tx_id,
+            kind: NormalTransaction::Deposit {
+                amount: amount(value),
+                currency: Currency::default(),
+            },
+            timestamp: Some(format!("2024-01-{day:02}T12:00:00Z").parse().unwrap()),
+        }
+    }
+
+    fn policy() -> InterestPolicy {
+        InterestPolicy::new(
+            vec![
+                RateTier {
+                    from: amount("1000"),
+                    bps: 200,
+                },
+                RateTier {
+                    from: Amount::ZERO,
+                    bps: 100,
+                },
+            ],
+            1800,
+        )
+    }
+
+    #[test]
+    fn tiered_daily_rate() {
+        let policy = policy();
+        assert_eq!(policy.daily(amount("500")), amount("500") * 100);
+        assert_eq!(
+            policy.daily(amount("1500")),
+            amount("1000") * 100 + amount("500") * 200
+        );
+        assert_eq!(policy.daily(amount("-10")), amount("-10") * 1800);
+    }
+
+    #[test]
+    fn day_count_defaults_to_365() {
+        assert_eq!(InterestPolicy::default().day_count, 365);
+        let policy = InterestPolicy {
+            day_count: 0,
+            ..policy()
+        };
+        assert_eq!(policy.denominator(), 10_000);
+    }
+
+    #[test]
+    fn rounds_half_to_even() {
+        // 0.00025, 0.00075 and -0.00075 are exact ties
+        assert_eq!(round(amount("1"), 4000), amount("0.0002"));
+        assert_eq!(round(amount("3"), 4000), amount("0.0008"));
+        assert_eq!(round(amount("-3"), 4000), amount("-0.0008"));
+        assert_eq!(round(amount("1"), 3), amount("0.3333"));
+    }
+
+    #[test]
+    fn accrues_daily_and_posts() {
+        let mut ledger = Ledger::default().with_interest(policy());
+        ledger.handle_transaction(&deposit(1, 1, "365", 1)).unwrap();
+        ledger.handle_transaction(&deposit(2, 2, "1", 1)).unwrap();
+        // 10 days at 1% on 365 and 1
+        ledger
+            .handle_transaction(&deposit(1, 3, "1000", 11))
+            .unwrap();
+        let postings = ledger.post_interest();
+        assert_eq!(postings.len(), 2);
+        assert_eq!(postings[0].amount, amount("0.1"));
+        // 0.000274 rounds up to 0.0003
+        assert_eq!(postings[1].amount, amount("0.0003"));
+        assert_eq!(
+            ledger.interest_house(&Currency::default()),
+            -(postings[0].amount + postings[1].amount)
+        );
+        assert!(ledger.verify().unwrap().is_ok());
+
+        // the control record posts as well, the rounding remainder of client 2 is kept
+        ledger.accrue_interest(1);
+        ledger
+            .handle_transaction(&Transaction::Control {
+                kind: ControlTransaction::PostInterest,
+                timestamp: None,
+            })
+            .unwrap();
+        let available = |client| {
+            ledger
+                .get_account(&client)
+                .unwrap()
+                .total(&Currency::default())
+        };
+        assert_eq!(available(2), amount("1.0003"));
+        assert!(ledger.verify().unwrap().is_ok());
+    }
+}
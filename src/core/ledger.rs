@@ -1,30 +1,117 @@
-use std::collections::{btree_map, BTreeMap};
+use std::{collections::BTreeMap, io, time::Instant};
+
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tracing::{debug, debug_span, field, trace, Level};
 
 use super::{
     account::{Account, AccountStatus, Balance},
-    transaction::{NormalTransaction, SettlementTransaction, Transaction},
+    events::{Event, Subscriber},
+    fees::{FeeSchedule, FeeTotals},
+    fraud::{FraudPolicy, RecentDeposit},
+    index::{MemoryIndex, Tombstone, TxIndex, TxRecord, TxSpace},
+    interest::{Accruals, InterestPolicy},
+    lifecycle::{DisputeEvent, DisputeLifecycle, UnfreezePolicy},
+    limits::{LimitRules, Rejection, Velocity},
+    parking::{Parking, ParkingPolicy},
+    retention::{Eviction, RetentionPolicy},
+    review::PendingTx,
+    stats::{Ignored, ParseFailure, RecordKind, Skipped, Stats},
+    transaction::{ControlTransaction, NormalTransaction, SettlementTransaction, Transaction},
 };
-use crate::{common::TxId, Amount, ClientId};
+use crate::{common::TxId, Amount, ClientId, CsvAccount, CsvColumns, Currency, Timestamp};
 
+/// Dispute state of a deposit or transfer, see [`DisputeLifecycle`] for the transitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TransactionOutcomeKind {
     Applied,
     Disputed,
     Resolved,
     Chargeback,
+    /// the chargeback was reversed, funds are back with the client
+    ChargebackReversed,
+    /// disputed again after a reversed chargeback
+    PreArbitration,
+    /// charged back after pre-arbitration, final
+    SecondChargeback,
 }
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DepositOutcome {
-    kind: TransactionOutcomeKind,
-    amount: Amount,
+    pub(super) kind: TransactionOutcomeKind,
+    pub(super) amount: Amount,
+    pub(super) currency: Currency,
+    /// currently held by open disputes
+    pub(super) disputed: Amount,
+    /// released by resolves, can't be disputed again unless resolved deposits can be
+    /// disputed again, see [`DisputeLifecycle::with_redispute`]
+    pub(super) resolved: Amount,
+    /// part of `settled` that was charged back
+    pub(super) charged_back: Amount,
+    /// fees charged for the deposit and its chargeback
+    pub(super) fee: Amount,
+    /// see [`super::fraud::FraudPolicy`]
+    pub(super) score: u32,
+    pub(super) seq: u64,
+    pub(super) timestamp: Option<Timestamp>,
 }
 
 impl DepositOutcome {
-    fn applied(amount: Amount) -> Self {
+    pub(super) fn applied(
+        amount: Amount,
+        currency: Currency,
+        seq: u64,
+        timestamp: Option<Timestamp>,
+    ) -> Self {
         DepositOutcome {
             kind: TransactionOutcomeKind::Applied,
             amount,
+            currency,
+            disputed: Amount::ZERO,
+            resolved: Amount::ZERO,
+            charged_back: Amount::ZERO,
+            fee: Amount::ZERO,
+            score: 0,
+            seq,
+            timestamp,
+        }
+    }
+    pub fn kind(&self) -> &TransactionOutcomeKind {
+        &self.kind
+    }
+    pub fn amount(&self) -> Amount {
+        self.amount
+    }
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+    /// amount held by open disputes
+    pub fn disputed(&self) -> Amount {
+        self.disputed
+    }
+    /// amount that was never disputed, resolved or charged back
+    pub fn remaining(&self) -> Amount {
+        self.amount - self.disputed - self.resolved - self.charged_back
+    }
+    /// what the next dispute can hold, a re-dispute covers the resolved amount as well
+    fn disputable(&self) -> Amount {
+        match self.kind {
+            TransactionOutcomeKind::Resolved => self.remaining() + self.resolved,
+            _ => self.remaining(),
         }
     }
+    pub fn charged_back(&self) -> Amount {
+        self.charged_back
+    }
+    pub fn fee(&self) -> Amount {
+        self.fee
+    }
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
 }
 
 #[derive(Debug, Error)]
@@ -35,14 +122,30 @@ pub enum Errors {
     DisputedNonExistant,
     #[error("Account was frozen during the dispute. reason: Chargeback")]
     AccountFrozen,
+    #[error("Transaction index is unavailable: {0}")]
+    Index(#[from] io::Error),
+    #[error("Transaction {0} is past the dispute window")]
+    DisputeWindowExpired(TxId),
+    #[error("Transaction rejected: {0}")]
+    Rejected(#[from] Rejection),
+    #[error("Input record is invalid: {0}")]
+    Parse(ParseFailure),
 }
 
 pub type Result = std::result::Result<(), Errors>;
 
+#[derive(Default)]
 pub struct Client {
-    account: Account,
-    deposits: BTreeMap<TxId, DepositOutcome>,
-    withdrawals: BTreeMap<TxId, Amount>,
+    pub(super) account: Account,
+    /// transactions with charged back funds that weren't reversed
+    pub(super) chargebacks: usize,
+    /// chargebacks reversed so far, the account may stay frozen after them
+    pub(super) reversals: usize,
+    pub(super) disputes: usize,
+    /// net amount charged back from the client per currency, reversals give it back
+    pub(super) charged_back: BTreeMap<Currency, Amount>,
+    pub(super) last_deposit: Option<RecentDeposit>,
+    pub(super) velocity: Velocity,
 }
 
 impl Client {
@@ -52,129 +155,596 @@ impl Client {
             AccountStatus::Active => Ok(()),
         }
     }
-    fn handle_dispute(&mut self, tx_id: TxId) -> Result {
-        if let btree_map::Entry::Occupied(mut tx) = self.deposits.entry(tx_id) {
-            let tx = tx.get_mut();
-            if matches!(tx.kind, TransactionOutcomeKind::Applied) {
-                tx.kind = TransactionOutcomeKind::Disputed;
-                self.account.hold(tx.amount);
+    // `next` is the state allowed by the lifecycle. amounts above what's left to dispute
+    // (or settle) are ignored like overdrawn withdrawals, partial settlements keep the state
+    // until nothing is held anymore
+    fn handle_dispute(
+        &mut self,
+        tx: &mut DepositOutcome,
+        amount: Option<Amount>,
+        next: TransactionOutcomeKind,
+    ) -> Result {
+        let disputable = tx.disputable();
+        let amount = amount.unwrap_or(disputable);
+        if amount > Amount::ZERO && amount <= disputable {
+            if matches!(tx.kind, TransactionOutcomeKind::Resolved) {
+                tx.resolved = Amount::ZERO;
             }
+            tx.disputed += amount;
+            tx.kind = next;
+            debug!(%amount, %disputable, "holding disputed funds");
+            self.account.hold(tx.currency, amount);
+        } else {
+            debug!(%amount, %disputable, "dispute amount isn't disputable");
         }
         Ok(())
     }
-    fn handle_resolve(&mut self, tx_id: TxId) -> Result {
-        if let btree_map::Entry::Occupied(mut tx) = self.deposits.entry(tx_id) {
-            let tx = tx.get_mut();
-            if matches!(tx.kind, TransactionOutcomeKind::Disputed) {
-                tx.kind = TransactionOutcomeKind::Resolved;
-                self.account.resolve(tx.amount);
+    fn handle_resolve(
+        &mut self,
+        tx: &mut DepositOutcome,
+        amount: Option<Amount>,
+        next: TransactionOutcomeKind,
+    ) -> Result {
+        let amount = amount.unwrap_or(tx.disputed);
+        if amount > Amount::ZERO && amount <= tx.disputed {
+            tx.disputed -= amount;
+            tx.resolved += amount;
+            if tx.disputed == Amount::ZERO {
+                tx.kind = next;
             }
+            debug!(%amount, still_disputed = %tx.disputed, "releasing held funds");
+            self.account.resolve(tx.currency, amount);
+        } else {
+            debug!(%amount, disputed = %tx.disputed, "resolve amount isn't disputed");
         }
         Ok(())
     }
-    fn handle_chargeback(&mut self, liabilities: &mut Balance, tx_id: TxId) -> Result {
+    fn handle_chargeback(
+        &mut self,
+        liabilities: &mut Balance,
+        tx: &mut DepositOutcome,
+        amount: Option<Amount>,
+        next: TransactionOutcomeKind,
+    ) -> Result {
         let prev_status = self.account.status;
-        if let btree_map::Entry::Occupied(mut tx) = self.deposits.entry(tx_id) {
-            let tx = tx.get_mut();
-            if matches!(tx.kind, TransactionOutcomeKind::Disputed) {
-                tx.kind = TransactionOutcomeKind::Chargeback;
-
-                self.account.chargeback(liabilities, tx.amount);
+        let amount = amount.unwrap_or(tx.disputed);
+        if amount > Amount::ZERO && amount <= tx.disputed {
+            if tx.charged_back == Amount::ZERO {
+                self.chargebacks += 1;
+            }
+            tx.disputed -= amount;
+            tx.charged_back += amount;
+            if tx.disputed == Amount::ZERO {
+                tx.kind = next;
             }
+            debug!(%amount, still_disputed = %tx.disputed, "charging back");
+            self.account.chargeback(liabilities, tx.currency, amount);
+        } else {
+            debug!(%amount, disputed = %tx.disputed, "chargeback amount isn't disputed");
         }
         let new_status = self.account.status;
         match (prev_status, new_status) {
-            (AccountStatus::Active, AccountStatus::Frozen) => self.wrap_error(),
+            (AccountStatus::Active, AccountStatus::Frozen) => {
+                debug!("account frozen by the chargeback");
+                self.wrap_error()
+            }
             _ => Ok(()),
         }
     }
+    /// re-credits charged back funds from `source`
+    fn handle_reversal(
+        &mut self,
+        source: &mut Balance,
+        tx: &mut DepositOutcome,
+        amount: Option<Amount>,
+        next: TransactionOutcomeKind,
+        unfreeze: UnfreezePolicy,
+    ) -> Result {
+        let amount = amount.unwrap_or(tx.charged_back);
+        if amount > Amount::ZERO && amount <= tx.charged_back {
+            tx.charged_back -= amount;
+            if tx.charged_back == Amount::ZERO {
+                tx.kind = next;
+                self.chargebacks -= 1;
+                self.reversals += 1;
+            }
+            debug!(%amount, still_charged_back = %tx.charged_back, "reversing chargeback");
+            self.account.deposit(source, tx.currency, amount);
+            if unfreeze == UnfreezePolicy::WhenReversed && self.chargebacks == 0 {
+                debug!("account unfrozen by the reversal");
+                self.account.status = AccountStatus::Active;
+            }
+        } else {
+            debug!(%amount, charged_back = %tx.charged_back, "reversal amount isn't charged back");
+        }
+        Ok(())
+    }
 }
 
-#[derive(Default)]
 pub struct Ledger {
-    liabilites: Balance,
-    accounts: BTreeMap<ClientId, Client>,
+    /// counterpart of the client balances, one per currency
+    pub(super) liabilites: BTreeMap<Currency, Balance>,
+    pub(super) accounts: BTreeMap<ClientId, Client>,
+    pub(super) index: Box<dyn TxIndex>,
+    pub(super) sequence: u64,
+    pub(super) clock: Option<Timestamp>,
+    pub(super) retention: RetentionPolicy,
+    pub(super) evicted: Eviction,
+    pub(super) interest: InterestPolicy,
+    pub(super) accruals: Accruals,
+    /// pays (and collects) interest, one per currency
+    pub(super) interest_house: BTreeMap<Currency, Balance>,
+    pub(super) fees: FeeSchedule,
+    pub(super) fee_totals: BTreeMap<(ClientId, Currency), FeeTotals>,
+    /// collects fees, one per currency
+    pub(super) revenue: BTreeMap<Currency, Balance>,
+    /// net amount of charged back deposits per currency, the part of the liabilities they
+    /// account for
+    pub(super) charged_back: BTreeMap<Currency, Amount>,
+    pub(super) limits: LimitRules,
+    pub(super) fraud: FraudPolicy,
+    pub(super) lifecycle: DisputeLifecycle,
+    /// transactions waiting for review
+    pub(super) pending: BTreeMap<(ClientId, TxId), PendingTx>,
+    pub(super) parking_policy: ParkingPolicy,
+    /// settlements waiting for the transaction they reference
+    pub(super) parking: Parking,
+    pub(super) stats: Stats,
+    /// outcome of the transaction being handled, `None` once applied
+    pub(super) skipped: Option<Skipped>,
+    #[cfg(feature = "metrics")]
+    pub(super) metrics: super::metrics::Metrics,
+    pub(super) subscribers: Vec<Box<dyn Subscriber>>,
+    /// rebuilt by [`Ledger::from_events`], the balances have no transaction history behind them
+    pub(super) projected: bool,
+}
+
+impl Default for Ledger {
+    fn default() -> Self {
+        Self::with_index(MemoryIndex::default())
+    }
 }
 
 impl Ledger {
-    fn handle_settlement_transaction(
+    /// Creates an empty ledger that keeps its deposit/withdrawal history in `index`.
+    pub fn with_index(index: impl TxIndex + 'static) -> Self {
+        Self {
+            liabilites: BTreeMap::new(),
+            accounts: BTreeMap::new(),
+            index: Box::new(index),
+            sequence: 0,
+            clock: None,
+            retention: RetentionPolicy::default(),
+            evicted: Eviction::default(),
+            interest: InterestPolicy::default(),
+            accruals: Accruals::default(),
+            interest_house: BTreeMap::new(),
+            fees: FeeSchedule::default(),
+            fee_totals: BTreeMap::new(),
+            revenue: BTreeMap::new(),
+            charged_back: BTreeMap::new(),
+            limits: LimitRules::default(),
+            fraud: FraudPolicy::default(),
+            lifecycle: DisputeLifecycle::default(),
+            pending: BTreeMap::new(),
+            parking_policy: ParkingPolicy::default(),
+            parking: Parking::default(),
+            stats: Stats::default(),
+            skipped: None,
+            #[cfg(feature = "metrics")]
+            metrics: Default::default(),
+            subscribers: Vec::new(),
+            projected: false,
+        }
+    }
+
+    pub fn with_lifecycle(mut self, lifecycle: DisputeLifecycle) -> Self {
+        self.lifecycle = lifecycle;
+        self
+    }
+
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Number of transactions handled so far, every indexed record remembers the value at the time
+    /// it was applied.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Latest timestamp seen in the input, `None` if no transaction had one.
+    pub fn clock(&self) -> Option<Timestamp> {
+        self.clock
+    }
+
+    pub(super) fn handle_settlement_transaction(
         &mut self,
         client_id: ClientId,
         tx_id: TxId,
         tx: &SettlementTransaction,
+        amount: Option<Amount>,
     ) -> Result {
-        if let Some(client) = self.accounts.get_mut(&client_id) {
-            match *tx {
-                SettlementTransaction::Dispute => client.handle_dispute(tx_id),
-                SettlementTransaction::Resolve => client.handle_resolve(tx_id),
-                SettlementTransaction::Chargeback => {
-                    client.handle_chargeback(&mut self.liabilites, tx_id)
+        if !self.accounts.contains_key(&client_id) {
+            self.park(client_id, tx_id, *tx, amount);
+            return Ok(());
+        }
+        // disputed funds are held by the client that received them
+        let (holder, mut deposit) = match self.index.get(client_id, tx_id, TxSpace::Disputable)? {
+            Some(TxRecord::Deposit(deposit)) => (client_id, deposit),
+            Some(TxRecord::Transfer { to, outcome }) => (to, outcome),
+            Some(TxRecord::Withdrawal { .. }) => unreachable!("withdrawals have their own space"),
+            None if self.is_known_tx(client_id, tx_id, TxSpace::Withdrawal)? => {
+                self.skip(Ignored::NotDisputable);
+                return Ok(());
+            }
+            None => {
+                // records expire undisputed and are evicted once settled, settling them again
+                // isn't a valid transition
+                match self
+                    .index
+                    .tombstone(client_id, tx_id, TxSpace::Disputable)?
+                {
+                    Some(Tombstone::Expired) if *tx == SettlementTransaction::Dispute => {
+                        return Err(Errors::DisputeWindowExpired(tx_id));
+                    }
+                    Some(_) => self.skip(Ignored::InvalidTransition),
+                    None => self.park(client_id, tx_id, *tx, amount),
+                }
+                return Ok(());
+            }
+        };
+        let event = DisputeEvent::of(tx, &deposit);
+        let Some(next) = self.lifecycle.next(deposit.kind, event) else {
+            debug!(state = ?deposit.kind, ?event, "no transition");
+            self.skip(Ignored::InvalidTransition);
+            return Ok(());
+        };
+        debug!(holder, from = ?deposit.kind, to = ?next, "dispute transition");
+        let disputable = deposit.disputable();
+        let opens_dispute = event == DisputeEvent::Dispute
+            && disputable > Amount::ZERO
+            && amount.is_none_or(|x| x <= disputable);
+        if opens_dispute
+            && self.retention.expired(
+                (deposit.seq, deposit.timestamp),
+                (self.sequence, self.clock),
+            )
+        {
+            return Err(Errors::DisputeWindowExpired(tx_id));
+        }
+        if opens_dispute {
+            let held = amount.unwrap_or(disputable);
+            self.check_dispute(holder, deposit.currency, held)?;
+        }
+        let Some(client) = self.accounts.get_mut(&holder) else {
+            self.skip(Ignored::UnknownClient);
+            return Ok(());
+        };
+        let was_frozen = matches!(client.account.status, AccountStatus::Frozen);
+        let prev = deposit.clone();
+        let result = match *tx {
+            SettlementTransaction::Dispute => client.handle_dispute(&mut deposit, amount, next),
+            SettlementTransaction::Resolve => client.handle_resolve(&mut deposit, amount, next),
+            SettlementTransaction::Chargeback if holder == client_id => {
+                let liabilities = self.liabilites.entry(deposit.currency).or_default();
+                client.handle_chargeback(liabilities, &mut deposit, amount, next)
+            }
+            SettlementTransaction::Chargeback => {
+                // a charged back transfer goes back to the sender
+                let mut returned = Balance::default();
+                let result = client.handle_chargeback(&mut returned, &mut deposit, amount, next);
+                if let Some(sender) = self.accounts.get_mut(&client_id) {
+                    let amount = returned.amount;
+                    sender
+                        .account
+                        .deposit(&mut returned, deposit.currency, amount);
+                }
+                result
+            }
+            SettlementTransaction::Reversal if holder == client_id => {
+                let liabilities = self.liabilites.entry(deposit.currency).or_default();
+                let unfreeze = self.lifecycle.unfreeze;
+                client.handle_reversal(liabilities, &mut deposit, amount, next, unfreeze)
+            }
+            SettlementTransaction::Reversal => {
+                // the sender got a charged back transfer, the reversal takes it back
+                let mut returned = Balance::default();
+                let reversed = amount.unwrap_or(deposit.charged_back);
+                if reversed > Amount::ZERO && reversed <= deposit.charged_back {
+                    if let Some(sender) = self.accounts.get_mut(&client_id) {
+                        sender
+                            .account
+                            .debit(&mut returned, deposit.currency, reversed);
+                    }
+                }
+                let Some(client) = self.accounts.get_mut(&holder) else {
+                    return Ok(());
+                };
+                let unfreeze = self.lifecycle.unfreeze;
+                client.handle_reversal(&mut returned, &mut deposit, amount, next, unfreeze)
+            }
+        };
+        if prev != deposit {
+            self.emit_settlement(client_id, holder, tx, &prev, &deposit, was_frozen);
+            if deposit.disputed > prev.disputed {
+                if let Some(client) = self.accounts.get_mut(&client_id) {
+                    client.disputes += 1;
+                }
+            }
+            if deposit.charged_back != prev.charged_back {
+                let change = deposit.charged_back - prev.charged_back;
+                if let Some(client) = self.accounts.get_mut(&holder) {
+                    *client.charged_back.entry(deposit.currency).or_default() += change;
+                }
+                // charged back transfers go back to the sender, not to the liabilities
+                if holder == client_id {
+                    *self.charged_back.entry(deposit.currency).or_default() += change;
                 }
             }
+            if deposit.charged_back > prev.charged_back {
+                let penalty = self.fees.chargeback_penalty;
+                self.charge_fee(holder, deposit.currency, penalty, |x| &mut x.chargebacks);
+                deposit.fee += penalty;
+            }
+            let record = if holder == client_id {
+                TxRecord::Deposit(deposit)
+            } else {
+                TxRecord::Transfer {
+                    to: holder,
+                    outcome: deposit,
+                }
+            };
+            self.index.insert(client_id, tx_id, record)?;
         } else {
-            Ok(())
+            self.skip(Ignored::InvalidAmount);
         }
+        result
     }
 
-    fn handle_normal_transaction(
+    pub(super) fn handle_normal_transaction(
         &mut self,
         client_id: ClientId,
         tx_id: TxId,
         tx: &NormalTransaction,
-    ) {
+        timestamp: Option<Timestamp>,
+        score: u32,
+    ) -> Result {
+        let known = match self.accounts.get(&client_id) {
+            Some(client) if !matches!(client.account.status, AccountStatus::Active) => {
+                self.skip(Ignored::FrozenAccount);
+                return Ok(());
+            }
+            client => client.is_some(),
+        };
         match *tx {
-            NormalTransaction::Deposit { amount } => {
-                self.accounts
-                    .entry(client_id)
-                    .and_modify(|client| {
-                        if !matches!(client.account.status, AccountStatus::Active) {
-                            return;
-                        }
-                        client.deposits.entry(tx_id).or_insert_with(|| {
-                            client.account.deposit(&mut self.liabilites, amount);
-                            DepositOutcome::applied(amount)
-                        });
-                    })
-                    .or_insert_with(|| {
-                        let mut account = Account::default();
-                        account.deposit(&mut self.liabilites, amount);
-                        let mut txs = BTreeMap::new();
-                        txs.insert(tx_id, DepositOutcome::applied(amount));
-                        Client {
-                            account,
-                            deposits: txs,
-                            withdrawals: Default::default(),
-                        }
-                    });
+            NormalTransaction::Deposit { amount, currency } => {
+                // a new client's tx id may already wait for review
+                if self.is_known_tx(client_id, tx_id, TxSpace::Disputable)? {
+                    self.skip(Ignored::DuplicateTx);
+                    return Ok(());
+                }
+                self.check_deposit(client_id)?;
+                let client = self.accounts.entry(client_id).or_default();
+                let liabilities = self.liabilites.entry(currency).or_default();
+                client.account.deposit(liabilities, currency, amount);
+                *self.stats.deposited.entry(currency).or_default() += amount;
+                client.last_deposit = Some(RecentDeposit {
+                    amount,
+                    currency,
+                    timestamp,
+                });
+                if !known {
+                    self.emit(Event::AccountOpened { client: client_id });
+                }
+                self.emit(Event::FundsDeposited {
+                    client: client_id,
+                    currency,
+                    amount,
+                });
+                let mut outcome =
+                    DepositOutcome::applied(amount, currency, self.sequence, timestamp);
+                outcome.fee = self.fees.deposit_fee(amount);
+                outcome.score = score;
+                debug!(%amount, %currency, fee = %outcome.fee, "deposited");
+                self.charge_fee(client_id, currency, outcome.fee, |x| &mut x.deposits);
+                self.note_deposit(client_id);
+                self.index
+                    .insert(client_id, tx_id, TxRecord::Deposit(outcome))?;
+                self.stats.indexed += 1;
+                self.replay_parked(client_id, tx_id)?;
             }
-            NormalTransaction::Withdraw { amount } => {
-                self.accounts.entry(client_id).and_modify(|client| {
-                    if !matches!(client.account.status, AccountStatus::Active) {
-                        return;
-                    }
-                    client.withdrawals.entry(tx_id).or_insert_with(|| {
-                        client.account.withdraw(&mut self.liabilites, amount);
-                        amount
+            NormalTransaction::Withdraw { amount, currency } => {
+                if !known {
+                    self.skip(Ignored::UnknownClient);
+                    return Ok(());
+                }
+                if self.is_known_tx(client_id, tx_id, TxSpace::Withdrawal)? {
+                    self.skip(Ignored::DuplicateTx);
+                    return Ok(());
+                }
+                let fee = self.fees.withdrawal;
+                self.check_withdrawal(client_id, currency, amount, fee)?;
+                let credit = self.limits.credit(client_id);
+                let Some(client) = self.accounts.get_mut(&client_id) else {
+                    return Ok(());
+                };
+                let funded = client
+                    .account
+                    .wallet(&currency)
+                    .is_some_and(|wallet| wallet.available.amount + credit >= amount + fee);
+                let fee = if funded {
+                    let liabilities = self.liabilites.entry(currency).or_default();
+                    client
+                        .account
+                        .withdraw(liabilities, currency, amount, credit);
+                    self.emit(Event::FundsWithdrawn {
+                        client: client_id,
+                        currency,
+                        amount,
                     });
+                    self.charge_fee(client_id, currency, fee, |x| &mut x.withdrawals);
+                    self.note_withdrawal(client_id, currency, amount);
+                    *self.stats.withdrawn.entry(currency).or_default() += amount;
+                    debug!(%amount, %currency, %fee, %credit, "withdrew");
+                    fee
+                } else {
+                    self.skip(Ignored::InsufficientFunds);
+                    Amount::ZERO
+                };
+                let record = TxRecord::Withdrawal {
+                    amount,
+                    currency,
+                    fee,
+                    score,
+                    seq: self.sequence,
+                    timestamp,
+                };
+                self.index.insert(client_id, tx_id, record)?;
+                self.stats.indexed += 1;
+            }
+            NormalTransaction::Transfer {
+                amount,
+                currency,
+                to,
+            } => {
+                let Some(client) = self.accounts.get(&client_id) else {
+                    self.skip(Ignored::UnknownClient);
+                    return Ok(());
+                };
+                let credit = self.limits.credit(client_id);
+                let funded = client
+                    .account
+                    .wallet(&currency)
+                    .is_some_and(|wallet| wallet.available.amount + credit >= amount);
+                let receiver_frozen = self
+                    .accounts
+                    .get(&to)
+                    .is_some_and(|x| !matches!(x.account.status, AccountStatus::Active));
+                let ignored = if !funded {
+                    Some(Ignored::InsufficientFunds)
+                } else if receiver_frozen {
+                    Some(Ignored::FrozenAccount)
+                } else if to == client_id {
+                    Some(Ignored::SelfTransfer)
+                } else if self.is_known_tx(client_id, tx_id, TxSpace::Disputable)? {
+                    Some(Ignored::DuplicateTx)
+                } else {
+                    None
+                };
+                if let Some(reason) = ignored {
+                    self.skip(reason);
+                    return Ok(());
+                }
+                self.check_withdrawal(client_id, currency, amount, Amount::ZERO)?;
+                let mut in_flight = Balance::default();
+                if let Some(sender) = self.accounts.get_mut(&client_id) {
+                    sender
+                        .account
+                        .withdraw(&mut in_flight, currency, amount, credit);
+                }
+                let opened = !self.accounts.contains_key(&to);
+                let receiver = self.accounts.entry(to).or_default();
+                receiver.account.deposit(&mut in_flight, currency, amount);
+                self.emit(Event::FundsWithdrawn {
+                    client: client_id,
+                    currency,
+                    amount,
+                });
+                if opened {
+                    self.emit(Event::AccountOpened { client: to });
+                }
+                self.emit(Event::FundsDeposited {
+                    client: to,
+                    currency,
+                    amount,
                 });
+                debug!(%amount, %currency, to, "transferred");
+                self.note_withdrawal(client_id, currency, amount);
+                let mut outcome =
+                    DepositOutcome::applied(amount, currency, self.sequence, timestamp);
+                outcome.score = score;
+                let record = TxRecord::Transfer { to, outcome };
+                self.index.insert(client_id, tx_id, record)?;
+                self.stats.indexed += 1;
+                self.replay_parked(client_id, tx_id)?;
             }
         }
+        Ok(())
     }
     pub fn handle_transaction(&mut self, tx: &Transaction) -> Result {
+        let kind = RecordKind::from(tx);
+        let span = debug_span!(
+            "transaction",
+            seq = self.sequence + 1,
+            client = tx.client_id(),
+            tx = tx.tx_id(),
+            ?kind,
+            outcome = field::Empty
+        );
+        let _entered = span.enter();
+        let started = Instant::now();
+        self.stats.started.get_or_insert(started);
+        let client = tx.client_id().filter(|_| tracing::enabled!(Level::TRACE));
+        if let Some(account) = client.and_then(|x| self.get_account(&x)) {
+            trace!(before = ?account.wallets, "balances");
+        }
+        let result = self.apply(tx);
+        self.tally(kind, &result);
+        if let Some(account) = client.and_then(|x| self.get_account(&x)) {
+            trace!(after = ?account.wallets, "balances");
+        }
+        #[cfg(feature = "metrics")]
+        self.metrics.observe(started.elapsed());
+        result
+    }
+    fn apply(&mut self, tx: &Transaction) -> Result {
+        self.sequence += 1;
+        self.clock = self.clock.max(tx.timestamp());
+        if let Some(clock) = self.clock {
+            self.accrue_until(clock.date_naive());
+        }
+        if self.retention.sweep_due(self.sequence) {
+            self.evict()?;
+        }
+        self.expire_parked();
         match tx {
             Transaction::Normal {
                 client_id,
                 tx_id,
                 kind,
+                timestamp,
             } => {
-                self.handle_normal_transaction(*client_id, *tx_id, kind);
-                Ok(())
+                let score = self.screen(tx)?;
+                if self.fraud.review_score.is_some_and(|limit| score >= limit) {
+                    return self.queue_for_review(*client_id, *tx_id, kind, *timestamp, score);
+                }
+                self.handle_normal_transaction(*client_id, *tx_id, kind, *timestamp, score)
             }
             Transaction::SettlementTransaction {
                 client_id,
                 tx_id,
                 kind,
-            } => self.handle_settlement_transaction(*client_id, *tx_id, kind),
+                amount,
+                ..
+            } => {
+                self.screen(tx)?;
+                self.handle_settlement_transaction(*client_id, *tx_id, kind, *amount)
+            }
+            Transaction::Review {
+                client_id,
+                tx_id,
+                kind,
+                ..
+            } => self.handle_review(*client_id, *tx_id, kind),
+            Transaction::Control {
+                kind: ControlTransaction::PostInterest,
+                ..
+            } => {
+                self.post_interest();
+                Ok(())
+            }
         }
     }
     pub fn entries(&self) -> impl Iterator<Item = (ClientId, &'_ Account)> {
@@ -182,9 +752,41 @@ impl Ledger {
             .iter()
             .map(|(id, client)| (*id, &client.account))
     }
+    /// Output rows of every account, one per (client, currency).
+    pub fn rows(&self) -> impl Iterator<Item = CsvAccount> + '_ {
+        self.entries().flat_map(|(id, account)| account.rows(id))
+    }
+    /// Optional report columns for the features in use: `currency` once a currency other than the
+    /// default one was seen, `credit_used` with credit limits and the pending ones with reviews.
+    pub fn report_columns(&self) -> CsvColumns {
+        CsvColumns {
+            currency: self.liabilites.keys().any(|x| *x != Currency::default()),
+            credit: self.limits.has_credit(),
+            pending: self.fraud.review_score.is_some(),
+        }
+    }
+    /// Liabilities of the ledger in a single currency, the negated sum of all client totals in it.
+    pub fn liabilities(&self, currency: &Currency) -> Amount {
+        self.liabilites
+            .get(currency)
+            .map_or(Amount::ZERO, |x| x.amount)
+    }
     pub fn get_account(&self, client_id: &ClientId) -> Option<&Account> {
         self.accounts.get(client_id).map(|x| &x.account)
     }
+    /// Deposits and withdrawals of a single client that are still retained, ordered by tx id.
+    pub fn history(&self, client_id: ClientId) -> io::Result<Vec<(TxId, TxRecord)>> {
+        let mut history = Vec::new();
+        self.index.for_client(client_id, &mut |tx_id, record| {
+            history.push((tx_id, record.clone()))
+        })?;
+        history.sort_by_key(|(tx_id, _)| *tx_id);
+        Ok(history)
+    }
+    /// Visits every retained deposit and withdrawal of every client.
+    pub fn visit_history(&self, f: &mut dyn FnMut(ClientId, TxId, &TxRecord)) -> io::Result<()> {
+        self.index.for_each(f)
+    }
 }
 
 #[cfg(test)]
@@ -192,29 +794,52 @@ mod test {
     use crate::{
         core::{
             account::{AccountStatus, Balance},
+            stats::Ignored,
             transaction::{NormalTransaction, SettlementTransaction, Transaction},
         },
-        Amount, ClientId, TxId,
+        Amount, ClientId, Currency, Timestamp, TxId,
     };
 
-    use super::Ledger;
+    use super::{DisputeLifecycle, Ledger, TransactionOutcomeKind, TxRecord, UnfreezePolicy};
 
     fn deposit(client_id: ClientId, tx_id: TxId, amount: &str) -> Transaction {
+        deposit_in(client_id, tx_id, amount, "")
+    }
+    fn deposit_in(client_id: ClientId, tx_id: TxId, amount: &str, currency: &str) -> Transaction {
         Transaction::Normal {
             client_id,
             tx_id,
             kind: NormalTransaction::Deposit {
                 amount: Amount::from_str(amount).unwrap(),
+                currency: currency.parse().unwrap(),
             },
+            timestamp: None,
         }
     }
     fn withdraw(client_id: ClientId, tx_id: TxId, amount: &str) -> Transaction {
+        withdraw_in(client_id, tx_id, amount, "")
+    }
+    fn withdraw_in(client_id: ClientId, tx_id: TxId, amount: &str, currency: &str) -> Transaction {
         Transaction::Normal {
             client_id,
             tx_id,
             kind: NormalTransaction::Withdraw {
                 amount: Amount::from_str(amount).unwrap(),
+                currency: currency.parse().unwrap(),
+            },
+            timestamp: None,
+        }
+    }
+    fn transfer(client_id: ClientId, tx_id: TxId, amount: &str, to: ClientId) -> Transaction {
+        Transaction::Normal {
+            client_id,
+            tx_id,
+            kind: NormalTransaction::Transfer {
+                amount: Amount::from_str(amount).unwrap(),
+                currency: Currency::default(),
+                to,
             },
+            timestamp: None,
         }
     }
     fn dispute(client_id: ClientId, tx_id: TxId, kind: SettlementTransaction) -> Transaction {
@@ -222,6 +847,22 @@ mod test {
             client_id,
             tx_id,
             kind,
+            amount: None,
+            timestamp: None,
+        }
+    }
+    fn dispute_part(
+        client_id: ClientId,
+        tx_id: TxId,
+        kind: SettlementTransaction,
+        amount: &str,
+    ) -> Transaction {
+        Transaction::SettlementTransaction {
+            client_id,
+            tx_id,
+            kind,
+            amount: Some(Amount::from_str(amount).unwrap()),
+            timestamp: None,
         }
     }
     fn execute_tx(ledger: &mut Ledger, txs: &[Transaction]) {
@@ -230,13 +871,15 @@ mod test {
         }
     }
     fn ledger_sanity_check(ledger: &mut Ledger) {
+        let currency = Currency::default();
         let accounts_total = ledger
             .entries()
-            .map(|x| x.1.total())
+            .map(|x| x.1.total(&currency))
             .reduce(std::ops::Add::add)
             .unwrap();
-        let liabilities = &ledger.liabilites;
-        assert_eq!(liabilities.amount + accounts_total, 0)
+        assert_eq!(ledger.liabilities(&currency) + accounts_total, 0);
+        let report = ledger.verify().unwrap();
+        assert!(report.is_ok(), "{report}");
     }
 
     fn assert_frozen(client_id: &ClientId, ledger: &Ledger) {
@@ -257,6 +900,7 @@ mod test {
     fn assert_available(client_id: &ClientId, ledger: &Ledger, amount: Amount) {
         let available = ledger
             .get_account(client_id)
+            .and_then(|x| x.wallet(&Currency::default()))
             .map(|x| &x.available)
             .expect("should be present");
         assert_eq!(*available, Balance { amount });
@@ -276,6 +920,25 @@ mod test {
         assert_active(&1, &ledger)
     }
 
+    #[test]
+    fn tx_id_reused_by_another_kind() {
+        // deposits and withdrawals of a client have their own tx ids, disputes refer to deposits
+        let mut ledger = Ledger::default();
+        let txs = [
+            deposit(1, 1, "10"),
+            withdraw(1, 1, "4"),
+            withdraw(1, 2, "4"),
+            deposit(1, 2, "7"),
+            withdraw(1, 2, "1"),
+            dispute(1, 1, SettlementTransaction::Dispute),
+        ];
+        execute_tx(&mut ledger, &txs);
+        ledger_sanity_check(&mut ledger);
+        assert_available(&1, &ledger, Amount::const_from_int(-1));
+        assert_eq!(ledger.stats().ignored[&Ignored::DuplicateTx], 1);
+        assert_eq!(ledger.history(1).unwrap().len(), 4);
+    }
+
     #[test]
     fn double_entry_resolve() {
         let mut ledger = Ledger::default();
@@ -304,6 +967,42 @@ mod test {
         assert_frozen(&1, &ledger)
     }
 
+    #[test]
+    fn history_with_timestamps() {
+        let mut ledger = Ledger::default();
+        let timestamp: Timestamp = "2024-03-01T12:30:00Z".parse().unwrap();
+        let mut deposit_tx = deposit(1, 1, "5.5");
+        if let Transaction::Normal { timestamp: ts, .. } = &mut deposit_tx {
+            *ts = Some(timestamp);
+        }
+        let txs = [
+            deposit(2, 3, "1"),
+            deposit_tx,
+            withdraw(1, 2, "1.5"),
+            dispute(1, 1, SettlementTransaction::Dispute),
+        ];
+        execute_tx(&mut ledger, &txs);
+        let history = ledger.history(1).unwrap();
+        assert_eq!(history.len(), 2);
+        let (tx_id, TxRecord::Deposit(deposit)) = &history[0] else {
+            panic!("expected a deposit first");
+        };
+        assert_eq!(*tx_id, 1);
+        assert_eq!(deposit.timestamp(), Some(timestamp));
+        assert_eq!(*deposit.kind(), TransactionOutcomeKind::Disputed);
+        assert!(matches!(
+            history[1],
+            (
+                2,
+                TxRecord::Withdrawal {
+                    timestamp: None,
+                    ..
+                }
+            )
+        ));
+        assert_eq!(ledger.clock(), Some(timestamp));
+    }
+
     #[test]
     fn double_entry_chargeback_multi() {
         let mut ledger = Ledger::default();
@@ -317,4 +1016,271 @@ mod test {
         ledger_sanity_check(&mut ledger);
         assert_frozen(&1, &ledger)
     }
+
+    #[test]
+    fn partial_disputes() {
+        let mut ledger = Ledger::default();
+        let txs = [
+            deposit(1, 1, "100"),
+            dispute_part(1, 1, SettlementTransaction::Dispute, "30"),
+            dispute_part(1, 1, SettlementTransaction::Dispute, "50"),
+            // only 20 left to dispute
+            dispute_part(1, 1, SettlementTransaction::Dispute, "25"),
+            dispute_part(1, 1, SettlementTransaction::Resolve, "10"),
+        ];
+        execute_tx(&mut ledger, &txs);
+        ledger_sanity_check(&mut ledger);
+        let held = |ledger: &Ledger| {
+            ledger.get_account(&1).unwrap().wallets[&Currency::default()]
+                .held
+                .amount
+        };
+        assert_eq!(held(&ledger), Amount::from_num(70));
+        assert_available(&1, &ledger, Amount::from_num(30));
+        let (_, TxRecord::Deposit(outcome)) = &ledger.history(1).unwrap()[0] else {
+            panic!("expected a deposit");
+        };
+        assert_eq!(*outcome.kind(), TransactionOutcomeKind::Disputed);
+        assert_eq!(outcome.remaining(), Amount::from_num(20));
+
+        // the resolved part can't be disputed again, the rest can
+        let txs = [
+            dispute_part(1, 1, SettlementTransaction::Chargeback, "40"),
+            dispute(1, 1, SettlementTransaction::Dispute),
+            dispute(1, 1, SettlementTransaction::Resolve),
+        ];
+        execute_tx(&mut ledger, &txs);
+        ledger_sanity_check(&mut ledger);
+        assert_eq!(held(&ledger), Amount::ZERO);
+        assert_available(&1, &ledger, Amount::from_num(60));
+        let (_, TxRecord::Deposit(outcome)) = &ledger.history(1).unwrap()[0] else {
+            panic!("expected a deposit");
+        };
+        assert_eq!(*outcome.kind(), TransactionOutcomeKind::Chargeback);
+        assert_eq!(outcome.charged_back(), Amount::from_num(40));
+        assert_eq!(outcome.remaining(), Amount::ZERO);
+        assert_frozen(&1, &ledger);
+    }
+
+    #[test]
+    fn redispute_after_partial_resolve() {
+        let mut ledger = Ledger::default();
+        let txs = [
+            deposit(1, 1, "100"),
+            dispute_part(1, 1, SettlementTransaction::Dispute, "30"),
+            dispute(1, 1, SettlementTransaction::Resolve),
+        ];
+        execute_tx(&mut ledger, &txs);
+        let (_, TxRecord::Deposit(outcome)) = &ledger.history(1).unwrap()[0] else {
+            panic!("expected a deposit");
+        };
+        // the undisputed 70 keep the deposit disputable
+        assert_eq!(*outcome.kind(), TransactionOutcomeKind::Applied);
+        assert_eq!(outcome.remaining(), Amount::from_num(70));
+
+        let txs = [
+            dispute_part(1, 1, SettlementTransaction::Dispute, "50"),
+            dispute(1, 1, SettlementTransaction::Dispute),
+        ];
+        execute_tx(&mut ledger, &txs);
+        ledger_sanity_check(&mut ledger);
+        let held = ledger.get_account(&1).unwrap().wallets[&Currency::default()]
+            .held
+            .amount;
+        assert_eq!(held, Amount::from_num(70));
+        assert_available(&1, &ledger, Amount::from_num(30));
+        assert!(!ledger
+            .stats()
+            .ignored
+            .contains_key(&Ignored::InvalidTransition));
+    }
+
+    #[test]
+    fn chargeback_reversal() {
+        for unfreeze in [UnfreezePolicy::Never, UnfreezePolicy::WhenReversed] {
+            let mut ledger = Ledger::default()
+                .with_lifecycle(DisputeLifecycle::default().with_unfreeze(unfreeze));
+            let txs = [
+                deposit(1, 1, "10"),
+                deposit(1, 2, "5"),
+                dispute(1, 1, SettlementTransaction::Dispute),
+                dispute(1, 1, SettlementTransaction::Chargeback),
+                dispute_part(1, 1, SettlementTransaction::Reversal, "4"),
+            ];
+            execute_tx(&mut ledger, &txs);
+            ledger_sanity_check(&mut ledger);
+            assert_available(&1, &ledger, Amount::from_num(9));
+            assert_frozen(&1, &ledger);
+
+            execute_tx(
+                &mut ledger,
+                &[dispute(1, 1, SettlementTransaction::Reversal)],
+            );
+            ledger_sanity_check(&mut ledger);
+            assert_available(&1, &ledger, Amount::from_num(15));
+            assert_eq!(
+                ledger.liabilities(&Currency::default()),
+                Amount::from_num(-15)
+            );
+            match unfreeze {
+                UnfreezePolicy::Never => assert_frozen(&1, &ledger),
+                UnfreezePolicy::WhenReversed => assert_active(&1, &ledger),
+            }
+            let (_, TxRecord::Deposit(outcome)) = &ledger.history(1).unwrap()[0] else {
+                panic!("expected a deposit");
+            };
+            assert_eq!(*outcome.kind(), TransactionOutcomeKind::ChargebackReversed);
+            assert_eq!(outcome.charged_back(), Amount::ZERO);
+
+            // pre-arbitration and the second chargeback
+            let txs = [
+                dispute(1, 1, SettlementTransaction::Dispute),
+                dispute(1, 1, SettlementTransaction::Chargeback),
+            ];
+            execute_tx(&mut ledger, &txs);
+            ledger_sanity_check(&mut ledger);
+            assert_available(&1, &ledger, Amount::from_num(5));
+            assert_frozen(&1, &ledger);
+            let (_, TxRecord::Deposit(outcome)) = &ledger.history(1).unwrap()[0] else {
+                panic!("expected a deposit");
+            };
+            assert_eq!(*outcome.kind(), TransactionOutcomeKind::SecondChargeback);
+        }
+    }
+
+    #[test]
+    fn partial_chargeback_reversal() {
+        let mut ledger = Ledger::default().with_lifecycle(
+            DisputeLifecycle::default().with_unfreeze(UnfreezePolicy::WhenReversed),
+        );
+        let txs = [
+            deposit(1, 1, "100"),
+            dispute(1, 1, SettlementTransaction::Dispute),
+            dispute_part(1, 1, SettlementTransaction::Chargeback, "40"),
+            dispute_part(1, 1, SettlementTransaction::Resolve, "60"),
+            dispute_part(1, 1, SettlementTransaction::Reversal, "40"),
+        ];
+        execute_tx(&mut ledger, &txs);
+        ledger_sanity_check(&mut ledger);
+        assert_available(&1, &ledger, Amount::from_num(100));
+        assert_active(&1, &ledger);
+        let (_, TxRecord::Deposit(outcome)) = &ledger.history(1).unwrap()[0] else {
+            panic!("expected a deposit");
+        };
+        assert_eq!(*outcome.kind(), TransactionOutcomeKind::ChargebackReversed);
+        assert_eq!(outcome.charged_back(), Amount::ZERO);
+    }
+
+    #[test]
+    fn transfer_chargeback_reversal() {
+        let mut ledger = Ledger::default();
+        let txs = [
+            deposit(1, 1, "10"),
+            transfer(1, 2, "4", 2),
+            dispute(1, 2, SettlementTransaction::Dispute),
+            dispute(1, 2, SettlementTransaction::Chargeback),
+            dispute(1, 2, SettlementTransaction::Reversal),
+        ];
+        execute_tx(&mut ledger, &txs);
+        ledger_sanity_check(&mut ledger);
+        assert_available(&1, &ledger, Amount::from_num(6));
+        assert_available(&2, &ledger, Amount::from_num(4));
+        assert_frozen(&2, &ledger);
+    }
+
+    #[test]
+    fn multi_currency() {
+        let mut ledger = Ledger::default();
+        let txs = [
+            deposit_in(1, 1, "10", "USD"),
+            deposit_in(1, 2, "5", "eur"),
+            deposit(1, 3, "1"),
+            // no EUR left after this, the second withdrawal is ignored
+            withdraw_in(1, 4, "5", "EUR"),
+            withdraw_in(1, 5, "1", "EUR"),
+            // there's no GBP wallet to withdraw from
+            withdraw_in(1, 6, "1", "GBP"),
+            dispute(1, 1, SettlementTransaction::Dispute),
+        ];
+        execute_tx(&mut ledger, &txs);
+        ledger_sanity_check(&mut ledger);
+        let usd: Currency = "USD".parse().unwrap();
+        let eur: Currency = "EUR".parse().unwrap();
+        let account = ledger.get_account(&1).unwrap();
+        assert_eq!(account.wallets.len(), 3);
+        let wallet = account.wallet(&usd).unwrap();
+        assert_eq!(
+            wallet.held,
+            Balance {
+                amount: Amount::const_from_int(10)
+            }
+        );
+        assert_eq!(wallet.available, Balance::default());
+        assert_eq!(account.total(&eur), Amount::ZERO);
+        assert_eq!(ledger.liabilities(&usd), Amount::const_from_int(-10));
+        assert_eq!(ledger.liabilities(&eur), Amount::ZERO);
+
+        execute_tx(
+            &mut ledger,
+            &[dispute(1, 1, SettlementTransaction::Chargeback)],
+        );
+        ledger_sanity_check(&mut ledger);
+        assert_frozen(&1, &ledger);
+        assert_eq!(ledger.liabilities(&usd), Amount::ZERO);
+        let rows: Vec<_> = ledger
+            .rows()
+            .map(|x| (x.currency, x.total, x.locked))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Currency::default(), Amount::ONE, true),
+                (eur, Amount::ZERO, true),
+                (usd, Amount::ZERO, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn transfers() {
+        let mut ledger = Ledger::default();
+        let txs = [
+            deposit(1, 1, "10"),
+            deposit(3, 2, "1"),
+            transfer(1, 3, "4", 2),
+            // insufficient funds, to itself and reused tx id are ignored
+            transfer(1, 4, "7", 2),
+            transfer(1, 5, "1", 1),
+            transfer(1, 3, "1", 2),
+            // frozen receiver
+            dispute(3, 2, SettlementTransaction::Dispute),
+            dispute(3, 2, SettlementTransaction::Chargeback),
+            transfer(1, 6, "1", 3),
+        ];
+        execute_tx(&mut ledger, &txs);
+        ledger_sanity_check(&mut ledger);
+        assert_available(&1, &ledger, Amount::const_from_int(6));
+        assert_available(&2, &ledger, Amount::const_from_int(4));
+
+        // disputed as a unit: held at the receiver, charged back to the sender
+        execute_tx(
+            &mut ledger,
+            &[dispute(1, 3, SettlementTransaction::Dispute)],
+        );
+        ledger_sanity_check(&mut ledger);
+        assert_available(&2, &ledger, Amount::ZERO);
+        execute_tx(
+            &mut ledger,
+            &[dispute(1, 3, SettlementTransaction::Chargeback)],
+        );
+        ledger_sanity_check(&mut ledger);
+        assert_available(&1, &ledger, Amount::const_from_int(10));
+        assert_eq!(
+            ledger.get_account(&2).unwrap().total(&Currency::default()),
+            0
+        );
+        assert_frozen(&2, &ledger);
+        assert_active(&1, &ledger);
+        assert_eq!(ledger.liabilities(&Currency::default()), -10);
+    }
 }
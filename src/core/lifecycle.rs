@@ -0,0 +1,383 @@
+use std::collections::BTreeMap;
+
+use super::{
+    ledger::{DepositOutcome, TransactionOutcomeKind},
+    transaction::SettlementTransaction,
+};
+use crate::Amount;
+
+/// Settlement events moving a deposit (or transfer) through its dispute lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DisputeEvent {
+    /// holds funds, opens pre-arbitration after a reversed chargeback
+    Dispute,
+    /// releases held funds back to the client, nothing of the deposit is left to dispute
+    Resolve,
+    /// releases held funds of a dispute that covered only part of the deposit,
+    /// the rest can still be disputed
+    ResolvePartialDispute,
+    /// releases the rest of the held funds after part of them was charged back
+    ResolveAfterChargeback,
+    /// takes held funds from the client, the first and the second (arbitration) one
+    Chargeback,
+    /// gives charged back funds back to the client
+    Reversal,
+}
+
+impl From<&SettlementTransaction> for DisputeEvent {
+    fn from(tx: &SettlementTransaction) -> Self {
+        match tx {
+            SettlementTransaction::Dispute => DisputeEvent::Dispute,
+            SettlementTransaction::Resolve => DisputeEvent::Resolve,
+            SettlementTransaction::Chargeback => DisputeEvent::Chargeback,
+            SettlementTransaction::Reversal => DisputeEvent::Reversal,
+        }
+    }
+}
+
+impl DisputeEvent {
+    /// Event of settling `deposit` with `tx`, a resolve is told apart by what's left of the deposit.
+    pub fn of(tx: &SettlementTransaction, deposit: &DepositOutcome) -> Self {
+        match DisputeEvent::from(tx) {
+            DisputeEvent::Resolve if deposit.charged_back() > Amount::ZERO => {
+                DisputeEvent::ResolveAfterChargeback
+            }
+            DisputeEvent::Resolve if deposit.remaining() > Amount::ZERO => {
+                DisputeEvent::ResolvePartialDispute
+            }
+            event => event,
+        }
+    }
+}
+
+/// What a chargeback reversal does to the frozen account.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnfreezePolicy {
+    /// the account stays frozen
+    #[default]
+    Never,
+    /// the account is unfrozen once none of its transactions is charged back anymore
+    WhenReversed,
+}
+
+/// Allowed transitions of [`TransactionOutcomeKind`], events without one are ignored.
+///
+/// The default lifecycle:
+///
+/// | from                  | event                    | to                    |
+/// |-----------------------|--------------------------|-----------------------|
+/// | `Applied`             | `Dispute`                | `Disputed`            |
+/// | `Disputed`            | `Dispute`                | `Disputed`            |
+/// | `Disputed`            | `Resolve`                | `Resolved`            |
+/// | `Disputed`            | `ResolvePartialDispute`  | `Applied`             |
+/// | `Disputed`            | `ResolveAfterChargeback` | `Chargeback`          |
+/// | `Disputed`            | `Chargeback`             | `Chargeback`          |
+/// | `Chargeback`          | `Reversal`               | `ChargebackReversed`  |
+/// | `ChargebackReversed`  | `Dispute`                | `PreArbitration`      |
+/// | `PreArbitration`      | `Dispute`                | `PreArbitration`      |
+/// | `PreArbitration`      | `Resolve`                | `ChargebackReversed`  |
+/// | `PreArbitration`      | `ResolvePartialDispute`  | `ChargebackReversed`  |
+/// | `PreArbitration`      | `ResolveAfterChargeback` | `SecondChargeback`    |
+/// | `PreArbitration`      | `Chargeback`             | `SecondChargeback`    |
+///
+/// Disputing an already disputed deposit holds another part of it. Resolves and chargebacks
+/// of only part of the held funds keep the current state until nothing is held anymore, the
+/// resolve ending a dispute is one of the three resolve events, see [`DisputeEvent::of`].
+#[derive(Debug, Clone)]
+pub struct DisputeLifecycle {
+    transitions: BTreeMap<(TransactionOutcomeKind, DisputeEvent), TransactionOutcomeKind>,
+    pub unfreeze: UnfreezePolicy,
+}
+
+impl Default for DisputeLifecycle {
+    fn default() -> Self {
+        use DisputeEvent as E;
+        use TransactionOutcomeKind as S;
+        Self {
+            transitions: BTreeMap::from([
+                ((S::Applied, E::Dispute), S::Disputed),
+                ((S::Disputed, E::Dispute), S::Disputed),
+                ((S::Disputed, E::Resolve), S::Resolved),
+                ((S::Disputed, E::ResolvePartialDispute), S::Applied),
+                ((S::Disputed, E::ResolveAfterChargeback), S::Chargeback),
+                ((S::Disputed, E::Chargeback), S::Chargeback),
+                ((S::Chargeback, E::Reversal), S::ChargebackReversed),
+                ((S::ChargebackReversed, E::Dispute), S::PreArbitration),
+                ((S::PreArbitration, E::Dispute), S::PreArbitration),
+                ((S::PreArbitration, E::Resolve), S::ChargebackReversed),
+                (
+                    (S::PreArbitration, E::ResolvePartialDispute),
+                    S::ChargebackReversed,
+                ),
+                (
+                    (S::PreArbitration, E::ResolveAfterChargeback),
+                    S::SecondChargeback,
+                ),
+                ((S::PreArbitration, E::Chargeback), S::SecondChargeback),
+            ]),
+            unfreeze: UnfreezePolicy::default(),
+        }
+    }
+}
+
+impl DisputeLifecycle {
+    /// A lifecycle without any transitions, every settlement is ignored.
+    pub fn empty() -> Self {
+        Self {
+            transitions: BTreeMap::new(),
+            unfreeze: UnfreezePolicy::default(),
+        }
+    }
+
+    pub fn allow(
+        mut self,
+        from: TransactionOutcomeKind,
+        event: DisputeEvent,
+        to: TransactionOutcomeKind,
+    ) -> Self {
+        self.transitions.insert((from, event), to);
+        self
+    }
+
+    pub fn forbid(mut self, from: TransactionOutcomeKind, event: DisputeEvent) -> Self {
+        self.transitions.remove(&(from, event));
+        self
+    }
+
+    /// Lets resolved deposits be disputed again, resolved amounts become disputable.
+    pub fn with_redispute(self) -> Self {
+        self.allow(
+            TransactionOutcomeKind::Resolved,
+            DisputeEvent::Dispute,
+            TransactionOutcomeKind::Disputed,
+        )
+    }
+
+    pub fn with_unfreeze(mut self, unfreeze: UnfreezePolicy) -> Self {
+        self.unfreeze = unfreeze;
+        self
+    }
+
+    pub fn next(
+        &self,
+        from: TransactionOutcomeKind,
+        event: DisputeEvent,
+    ) -> Option<TransactionOutcomeKind> {
+        self.transitions.get(&(from, event)).copied()
+    }
+
+    pub fn transitions(
+        &self,
+    ) -> impl Iterator<Item = (TransactionOutcomeKind, DisputeEvent, TransactionOutcomeKind)> + '_
+    {
+        self.transitions
+            .iter()
+            .map(|(&(from, event), &to)| (from, event, to))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            account::AccountStatus,
+            index::TxRecord,
+            ledger::{DepositOutcome, Ledger, TransactionOutcomeKind},
+            transaction::{NormalTransaction, SettlementTransaction, Transaction},
+        },
+        Amount, Currency,
+    };
+
+    use super::{DisputeEvent, DisputeLifecycle};
+
+    use DisputeEvent as E;
+    use TransactionOutcomeKind as S;
+
+    const STATES: [S; 7] = [
+        S::Applied,
+        S::Disputed,
+        S::Resolved,
+        S::Chargeback,
+        S::ChargebackReversed,
+        S::PreArbitration,
+        S::SecondChargeback,
+    ];
+
+    /// a ledger with a single deposit of 10 in `state`, balances match it
+    fn ledger_in(state: S, lifecycle: DisputeLifecycle) -> Ledger {
+        let mut ledger = Ledger::default().with_lifecycle(lifecycle);
+        let currency = Currency::default();
+        let amount = Amount::from_num(10);
+        ledger
+            .handle_transaction(&Transaction::Normal {
+                client_id: 1,
+                tx_id: 1,
+                kind: NormalTransaction::Deposit { amount, currency },
+                timestamp: None,
+            })
+            .unwrap();
+        let mut deposit = DepositOutcome::applied(amount, currency, 1, None);
+        deposit.kind = state;
+        let client = ledger.accounts.get_mut(&1).unwrap();
+        if matches!(state, S::ChargebackReversed | S::PreArbitration) {
+            client.reversals = 1;
+            client.account.status = AccountStatus::Frozen;
+        }
+        match state {
+            S::Applied | S::ChargebackReversed => {}
+            S::Disputed | S::PreArbitration => {
+                deposit.disputed = amount;
+                client.account.hold(currency, amount);
+            }
+            S::Resolved => deposit.resolved = amount,
+            S::Chargeback | S::SecondChargeback => {
+                deposit.charged_back = amount;
+                client.chargebacks = 1;
+                let liabilities = ledger.liabilites.entry(currency).or_default();
+                client.account.hold(currency, amount);
+                client.account.chargeback(liabilities, currency, amount);
+            }
+        }
+        ledger
+            .index
+            .insert(1, 1, TxRecord::Deposit(deposit))
+            .unwrap();
+        assert!(ledger.verify().unwrap().is_ok());
+        ledger
+    }
+
+    fn state(ledger: &Ledger) -> S {
+        match &ledger.history(1).unwrap()[0].1 {
+            TxRecord::Deposit(deposit) => *deposit.kind(),
+            _ => panic!("expected a deposit"),
+        }
+    }
+
+    #[test]
+    fn default_transitions() {
+        let lifecycle = DisputeLifecycle::default();
+        let transitions: Vec<_> = lifecycle.transitions().collect();
+        assert_eq!(
+            transitions,
+            vec![
+                (S::Applied, E::Dispute, S::Disputed),
+                (S::Disputed, E::Dispute, S::Disputed),
+                (S::Disputed, E::Resolve, S::Resolved),
+                (S::Disputed, E::ResolvePartialDispute, S::Applied),
+                (S::Disputed, E::ResolveAfterChargeback, S::Chargeback),
+                (S::Disputed, E::Chargeback, S::Chargeback),
+                (S::Chargeback, E::Reversal, S::ChargebackReversed),
+                (S::ChargebackReversed, E::Dispute, S::PreArbitration),
+                (S::PreArbitration, E::Dispute, S::PreArbitration),
+                (S::PreArbitration, E::Resolve, S::ChargebackReversed),
+                (
+                    S::PreArbitration,
+                    E::ResolvePartialDispute,
+                    S::ChargebackReversed
+                ),
+                (
+                    S::PreArbitration,
+                    E::ResolveAfterChargeback,
+                    S::SecondChargeback
+                ),
+                (S::PreArbitration, E::Chargeback, S::SecondChargeback),
+            ]
+        );
+        assert_eq!(lifecycle.next(S::Resolved, E::Dispute), None);
+        assert_eq!(
+            lifecycle.with_redispute().next(S::Resolved, E::Dispute),
+            Some(S::Disputed)
+        );
+    }
+
+    #[test]
+    fn partial_resolve_follows_table() {
+        let currency = Currency::default();
+        let settle = |kind, amount: Option<i32>| Transaction::SettlementTransaction {
+            client_id: 1,
+            tx_id: 1,
+            kind,
+            amount: amount.map(Amount::from_num),
+            timestamp: None,
+        };
+        for (lifecycle, expected) in [
+            (DisputeLifecycle::default(), S::Applied),
+            (
+                DisputeLifecycle::default().forbid(S::Disputed, E::ResolvePartialDispute),
+                S::Disputed,
+            ),
+            (
+                DisputeLifecycle::default().allow(
+                    S::Disputed,
+                    E::ResolvePartialDispute,
+                    S::Resolved,
+                ),
+                S::Resolved,
+            ),
+        ] {
+            let mut ledger = Ledger::default().with_lifecycle(lifecycle);
+            let txs = [
+                Transaction::Normal {
+                    client_id: 1,
+                    tx_id: 1,
+                    kind: NormalTransaction::Deposit {
+                        amount: Amount::from_num(10),
+                        currency,
+                    },
+                    timestamp: None,
+                },
+                settle(SettlementTransaction::Dispute, Some(4)),
+                settle(SettlementTransaction::Resolve, None),
+            ];
+            for tx in &txs {
+                ledger.handle_transaction(tx).unwrap();
+            }
+            assert_eq!(state(&ledger), expected);
+            assert!(ledger.verify().unwrap().is_ok());
+        }
+    }
+
+    /// every settlement record in every state either follows the table or is ignored,
+    /// the ledger stays consistent either way
+    #[test]
+    fn exhaustive_transition_table() {
+        let events = [
+            SettlementTransaction::Dispute,
+            SettlementTransaction::Resolve,
+            SettlementTransaction::Chargeback,
+            SettlementTransaction::Reversal,
+        ];
+        for lifecycle in [
+            DisputeLifecycle::default(),
+            DisputeLifecycle::default().with_redispute(),
+            DisputeLifecycle::empty(),
+        ] {
+            for from in STATES {
+                for kind in &events {
+                    let event = DisputeEvent::from(kind);
+                    let mut ledger = ledger_in(from, lifecycle.clone());
+                    let _ = ledger.handle_transaction(&Transaction::SettlementTransaction {
+                        client_id: 1,
+                        tx_id: 1,
+                        kind: *kind,
+                        amount: None,
+                        timestamp: None,
+                    });
+                    let expected = lifecycle.next(from, event).unwrap_or(from);
+                    assert_eq!(state(&ledger), expected, "{from:?} + {event:?}");
+                    let report = ledger.verify().unwrap();
+                    assert!(report.is_ok(), "{from:?} + {event:?}: {report}");
+                    let frozen = matches!(
+                        ledger.get_account(&1).unwrap().status,
+                        AccountStatus::Frozen
+                    );
+                    // reversals don't unfreeze by default
+                    let charged_back = [from, expected]
+                        .iter()
+                        .any(|x| !matches!(x, S::Applied | S::Disputed | S::Resolved));
+                    assert_eq!(frozen, charged_back, "{from:?} + {event:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use chrono::{NaiveDate, TimeDelta};
+use thiserror::Error;
+
+use super::ledger::Ledger;
+use crate::{Amount, ClientId, Currency, Timestamp};
+
+/// Risk limits of a client, `None` means unlimited. Amounts are the same in every currency.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// largest single withdrawal or outgoing transfer
+    pub max_withdrawal: Option<Amount>,
+    /// total of withdrawals and outgoing transfers per calendar day of the ledger clock
+    pub max_daily_withdrawal: Option<Amount>,
+    /// deposits accepted within [`LimitRules::deposit_window`]
+    pub max_deposits: Option<u32>,
+    /// withdrawals and outgoing transfers can't take `available` below this
+    pub min_balance: Option<Amount>,
+    /// withdrawals and outgoing transfers may take `available` this far below zero
+    pub credit_limit: Option<Amount>,
+}
+
+impl Limits {
+    /// fills every unset limit from `defaults`
+    pub fn or(self, defaults: Limits) -> Limits {
+        Limits {
+            max_withdrawal: self.max_withdrawal.or(defaults.max_withdrawal),
+            max_daily_withdrawal: self.max_daily_withdrawal.or(defaults.max_daily_withdrawal),
+            max_deposits: self.max_deposits.or(defaults.max_deposits),
+            min_balance: self.min_balance.or(defaults.min_balance),
+            credit_limit: self.credit_limit.or(defaults.credit_limit),
+        }
+    }
+}
+
+/// What happens to a dispute that would take `available` below the credit limit.
+/// Without a credit line the limit is zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisputeOverdraft {
+    /// funds are held anyway, `available` can go past the limit
+    #[default]
+    Allow,
+    /// the dispute is rejected with [`Rejection::CreditLimit`]
+    Reject,
+}
+
+/// Limits evaluated by the ledger before applying deposits, withdrawals and transfers.
+#[derive(Debug, Clone)]
+pub struct LimitRules {
+    pub defaults: Limits,
+    /// per client limits, unset ones fall back to `defaults`
+    pub overrides: BTreeMap<ClientId, Limits>,
+    /// sliding window for `max_deposits`, measured with transaction timestamps.
+    /// without timestamps the window never ends
+    pub deposit_window: TimeDelta,
+    pub dispute_overdraft: DisputeOverdraft,
+}
+
+impl Default for LimitRules {
+    fn default() -> Self {
+        Self {
+            defaults: Limits::default(),
+            overrides: BTreeMap::new(),
+            deposit_window: TimeDelta::days(1),
+            dispute_overdraft: DisputeOverdraft::default(),
+        }
+    }
+}
+
+impl LimitRules {
+    pub fn limits(&self, client_id: ClientId) -> Limits {
+        self.overrides
+            .get(&client_id)
+            .map_or(self.defaults, |x| x.or(self.defaults))
+    }
+
+    /// how far the client's `available` may go below zero
+    pub fn credit(&self, client_id: ClientId) -> Amount {
+        self.limits(client_id).credit_limit.unwrap_or_default()
+    }
+
+    /// whether any client has a credit line
+    pub fn has_credit(&self) -> bool {
+        std::iter::once(&self.defaults)
+            .chain(self.overrides.values())
+            .any(|x| x.credit_limit.is_some_and(|limit| limit > Amount::ZERO))
+    }
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    #[error("withdrawal of {amount} is above the limit of {limit}")]
+    WithdrawalLimit { amount: Amount, limit: Amount },
+    #[error("withdrawals of the day would total {total}, above the daily limit of {limit}")]
+    DailyWithdrawalLimit { total: Amount, limit: Amount },
+    #[error("already {limit} deposits within the deposit window")]
+    DepositVelocity { limit: u32 },
+    #[error("available balance would drop to {balance}, below the minimum of {min}")]
+    MinimumBalance { balance: Amount, min: Amount },
+    #[error("dispute would take available balance to {balance}, past the credit limit of {limit}")]
+    CreditLimit { balance: Amount, limit: Amount },
+    #[error("fraud check scored {score}: {reason}")]
+    Fraud { score: u32, reason: String },
+}
+
+/// Recent activity of a client the limits are checked against.
+#[derive(Debug, Default)]
+pub(super) struct Velocity {
+    day: Option<NaiveDate>,
+    withdrawn: BTreeMap<Currency, Amount>,
+    /// times of the deposits within the window, oldest first
+    deposits: VecDeque<Option<Timestamp>>,
+}
+
+impl Velocity {
+    fn withdrawn(&self, day: Option<NaiveDate>, currency: Currency) -> Amount {
+        if self.day != day {
+            return Amount::ZERO;
+        }
+        self.withdrawn.get(&currency).copied().unwrap_or_default()
+    }
+}
+
+impl Ledger {
+    pub fn with_limits(mut self, limits: LimitRules) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    fn today(&self) -> Option<NaiveDate> {
+        self.clock.map(|x| x.date_naive())
+    }
+
+    pub(super) fn check_deposit(&mut self, client_id: ClientId) -> Result<(), Rejection> {
+        let Some(limit) = self.limits.limits(client_id).max_deposits else {
+            return Ok(());
+        };
+        let window_start = self.clock.map(|now| now - self.limits.deposit_window);
+        let count = match self.accounts.get_mut(&client_id) {
+            Some(client) => {
+                let deposits = &mut client.velocity.deposits;
+                while deposits
+                    .front()
+                    .is_some_and(|&at| at.is_some() && at < window_start)
+                {
+                    deposits.pop_front();
+                }
+                deposits.len()
+            }
+            None => 0,
+        };
+        if count >= limit as usize {
+            return Err(Rejection::DepositVelocity { limit });
+        }
+        Ok(())
+    }
+
+    pub(super) fn note_deposit(&mut self, client_id: ClientId) {
+        if self.limits.limits(client_id).max_deposits.is_none() {
+            return;
+        }
+        let clock = self.clock;
+        if let Some(client) = self.accounts.get_mut(&client_id) {
+            client.velocity.deposits.push_back(clock);
+        }
+    }
+
+    /// `amount` leaves the client's available balance together with `fee`
+    pub(super) fn check_withdrawal(
+        &self,
+        client_id: ClientId,
+        currency: Currency,
+        amount: Amount,
+        fee: Amount,
+    ) -> Result<(), Rejection> {
+        let limits = self.limits.limits(client_id);
+        let Some(client) = self.accounts.get(&client_id) else {
+            return Ok(());
+        };
+        if let Some(limit) = limits.max_withdrawal.filter(|limit| amount > *limit) {
+            return Err(Rejection::WithdrawalLimit { amount, limit });
+        }
+        if let Some(limit) = limits.max_daily_withdrawal {
+            let total = client.velocity.withdrawn(self.today(), currency) + amount;
+            if total > limit {
+                return Err(Rejection::DailyWithdrawalLimit { total, limit });
+            }
+        }
+        if let Some(min) = limits.min_balance {
+            let available = client
+                .account
+                .wallet(&currency)
+                .map_or(Amount::ZERO, |x| x.available.amount);
+            let balance = available - amount - fee;
+            if balance < min {
+                return Err(Rejection::MinimumBalance { balance, min });
+            }
+        }
+        Ok(())
+    }
+
+    /// holding `amount` of a disputed deposit must respect the credit limit, see [`DisputeOverdraft`]
+    pub(super) fn check_dispute(
+        &self,
+        client_id: ClientId,
+        currency: Currency,
+        amount: Amount,
+    ) -> Result<(), Rejection> {
+        if self.limits.dispute_overdraft == DisputeOverdraft::Allow {
+            return Ok(());
+        }
+        let limit = self.limits.credit(client_id);
+        let available = self
+            .accounts
+            .get(&client_id)
+            .and_then(|client| client.account.wallet(&currency))
+            .map_or(Amount::ZERO, |x| x.available.amount);
+        let balance = available - amount;
+        if balance < -limit {
+            return Err(Rejection::CreditLimit { balance, limit });
+        }
+        Ok(())
+    }
+
+    pub(super) fn note_withdrawal(
+        &mut self,
+        client_id: ClientId,
+        currency: Currency,
+        amount: Amount,
+    ) {
+        if self.limits.limits(client_id).max_daily_withdrawal.is_none() {
+            return;
+        }
+        let today = self.today();
+        if let Some(client) = self.accounts.get_mut(&client_id) {
+            let velocity = &mut client.velocity;
+            if velocity.day != today {
+                velocity.day = today;
+                velocity.withdrawn.clear();
+            }
+            *velocity.withdrawn.entry(currency).or_default() += amount;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use chrono::TimeDelta;
+
+    use crate::{
+        core::{
+            ledger::{Errors, Ledger},
+            transaction::{NormalTransaction, SettlementTransaction, Transaction},
+        },
+        Amount, ClientId, Currency, Timestamp, TxId,
+    };
+
+    use super::{DisputeOverdraft, LimitRules, Limits, Rejection};
+
+    fn amount(x: &str) -> Amount {
+        Amount::from_str(x).unwrap()
+    }
+
+    fn tx(client_id: ClientId, tx_id: TxId, kind: NormalTransaction, hour: u32) -> Transaction {
+        Transaction::Normal {
+            client_id,
+            tx_id,
+            kind,
+            timestamp: Some(
+                "2024-01-01T00:00:00Z".parse::<Timestamp>().unwrap()
+                    + TimeDelta::hours(hour.into()),
+            ),
+        }
+    }
+
+    fn deposit(value: &str) -> NormalTransaction {
+        NormalTransaction::Deposit {
+            amount: amount(value),
+            currency: Currency::default(),
+        }
+    }
+
+    fn withdraw(value: &str) -> NormalTransaction {
+        NormalTransaction::Withdraw {
+            amount: amount(value),
+            currency: Currency::default(),
+        }
+    }
+
+    fn rejection(ledger: &mut Ledger, tx: Transaction) -> Option<Rejection> {
+        match ledger.handle_transaction(&tx) {
+            Ok(()) => None,
+            Err(Errors::Rejected(rejection)) => Some(rejection),
+            Err(err) => panic!("unexpected error {err}"),
+        }
+    }
+
+    fn ledger() -> Ledger {
+        Ledger::default().with_limits(LimitRules {
+            defaults: Limits {
+                max_withdrawal: Some(amount("50")),
+                max_daily_withdrawal: Some(amount("80")),
+                max_deposits: Some(2),
+                min_balance: Some(amount("10")),
+                credit_limit: None,
+            },
+            overrides: BTreeMap::from([(
+                2,
+                Limits {
+                    max_withdrawal: Some(amount("500")),
+                    ..Default::default()
+                },
+            )]),
+            deposit_window: TimeDelta::hours(24),
+            dispute_overdraft: DisputeOverdraft::Allow,
+        })
+    }
+
+    #[test]
+    fn withdrawal_limits() {
+        let mut ledger = ledger();
+        assert_eq!(rejection(&mut ledger, tx(1, 1, deposit("120"), 0)), None);
+        assert_eq!(
+            rejection(&mut ledger, tx(1, 2, withdraw("60"), 1)),
+            Some(Rejection::WithdrawalLimit {
+                amount: amount("60"),
+                limit: amount("50")
+            })
+        );
+        assert_eq!(rejection(&mut ledger, tx(1, 3, withdraw("50"), 1)), None);
+        assert_eq!(
+            rejection(&mut ledger, tx(1, 4, withdraw("40"), 2)),
+            Some(Rejection::DailyWithdrawalLimit {
+                total: amount("90"),
+                limit: amount("80")
+            })
+        );
+        // the next day starts over until the minimum balance is hit
+        assert_eq!(rejection(&mut ledger, tx(1, 5, withdraw("50"), 25)), None);
+        assert_eq!(
+            rejection(&mut ledger, tx(1, 6, withdraw("15"), 26)),
+            Some(Rejection::MinimumBalance {
+                balance: amount("5"),
+                min: amount("10")
+            })
+        );
+        let available = ledger.get_account(&1).unwrap().total(&Currency::default());
+        assert_eq!(available, amount("20"));
+        assert!(ledger.verify().unwrap().is_ok());
+    }
+
+    #[test]
+    fn deposit_velocity_and_overrides() {
+        let mut ledger = ledger();
+        assert_eq!(rejection(&mut ledger, tx(2, 1, deposit("1000"), 0)), None);
+        assert_eq!(rejection(&mut ledger, tx(2, 2, deposit("1"), 1)), None);
+        assert_eq!(
+            rejection(&mut ledger, tx(2, 3, deposit("1"), 2)),
+            Some(Rejection::DepositVelocity { limit: 2 })
+        );
+        // the first deposit left the window
+        assert_eq!(rejection(&mut ledger, tx(2, 4, deposit("1"), 25)), None);
+        // overridden single limit, default daily limit
+        assert_eq!(
+            rejection(&mut ledger, tx(2, 5, withdraw("100"), 25)),
+            Some(Rejection::DailyWithdrawalLimit {
+                total: amount("100"),
+                limit: amount("80")
+            })
+        );
+    }
+
+    #[test]
+    fn credit_line() {
+        let mut ledger = Ledger::default().with_limits(LimitRules {
+            defaults: Limits {
+                credit_limit: Some(amount("100")),
+                ..Default::default()
+            },
+            dispute_overdraft: DisputeOverdraft::Reject,
+            ..Default::default()
+        });
+        assert_eq!(rejection(&mut ledger, tx(1, 1, deposit("50"), 0)), None);
+        assert_eq!(rejection(&mut ledger, tx(1, 2, deposit("30"), 0)), None);
+        // past the credit line, ignored like any unfunded withdrawal
+        assert_eq!(rejection(&mut ledger, tx(1, 3, withdraw("190"), 1)), None);
+        assert_eq!(rejection(&mut ledger, tx(1, 4, withdraw("150"), 1)), None);
+        let wallet = ledger
+            .get_account(&1)
+            .unwrap()
+            .wallet(&Currency::default())
+            .unwrap();
+        assert_eq!(wallet.available.amount, amount("-70"));
+        assert_eq!(wallet.credit_used(), amount("70"));
+
+        let dispute = |tx_id| Transaction::SettlementTransaction {
+            client_id: 1,
+            tx_id,
+            kind: SettlementTransaction::Dispute,
+            amount: None,
+            timestamp: None,
+        };
+        assert_eq!(
+            rejection(&mut ledger, dispute(1)),
+            Some(Rejection::CreditLimit {
+                balance: amount("-120"),
+                limit: amount("100")
+            })
+        );
+        assert_eq!(rejection(&mut ledger, dispute(2)), None);
+        let rows: Vec<_> = ledger.rows().collect();
+        assert_eq!(rows[0].credit_used, amount("100"));
+        assert!(ledger.verify().unwrap().is_ok());
+    }
+}
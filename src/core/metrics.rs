@@ -0,0 +1,333 @@
+//! Prometheus metrics of the ledger, enabled by the `metrics` feature.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use super::{account::AccountStatus, ledger::Ledger, stats::RecordKind};
+
+/// how long the exporter serves a scraper, from accepting it to writing the response
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// upper bounds of the latency buckets in seconds
+const LATENCY_BUCKETS: [f64; 8] = [1e-6, 5e-6, 1e-5, 5e-5, 1e-4, 5e-4, 1e-3, 1e-2];
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// observations per bucket of [`LATENCY_BUCKETS`], not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+/// Counters the ledger only keeps for the exposition, the rest comes from [`super::stats`].
+#[derive(Debug, Default)]
+pub(super) struct Metrics {
+    transactions: BTreeMap<(RecordKind, &'static str), u64>,
+    latency: Histogram,
+}
+
+impl Metrics {
+    pub(super) fn count(&mut self, kind: RecordKind, outcome: &'static str) {
+        *self.transactions.entry((kind, outcome)).or_default() += 1;
+    }
+
+    pub(super) fn observe(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|le| secs <= *le) {
+            self.latency.buckets[bucket] += 1;
+        }
+        self.latency.sum += secs;
+        self.latency.count += 1;
+    }
+}
+
+/// snake_case name of a label value, the same one used in the JSON summary
+fn label<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(x)) => x,
+        _ => String::new(),
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+impl Ledger {
+    /// Current metrics in the Prometheus text format.
+    pub fn render_metrics(&self) -> String {
+        let mut out = String::new();
+        let stats = &self.stats;
+
+        header(
+            &mut out,
+            "ledger_transactions_total",
+            "counter",
+            "Transactions handled by kind and outcome.",
+        );
+        for ((kind, outcome), count) in &self.metrics.transactions {
+            let kind = label(kind);
+            let _ = writeln!(
+                out,
+                "ledger_transactions_total{{kind=\"{kind}\",outcome=\"{outcome}\"}} {count}"
+            );
+        }
+        header(
+            &mut out,
+            "ledger_ignored_total",
+            "counter",
+            "Transactions left without effect by reason.",
+        );
+        for (reason, count) in &stats.ignored {
+            let reason = label(reason);
+            let _ = writeln!(out, "ledger_ignored_total{{reason=\"{reason}\"}} {count}");
+        }
+        header(
+            &mut out,
+            "ledger_records_read_total",
+            "counter",
+            "Input records passed to the ledger.",
+        );
+        let _ = writeln!(out, "ledger_records_read_total {}", stats.records_read);
+        header(
+            &mut out,
+            "ledger_parse_failures_total",
+            "counter",
+            "Input records that couldn't be parsed by cause.",
+        );
+        for (cause, count) in &stats.parse_failures {
+            let cause = label(cause);
+            let _ = writeln!(
+                out,
+                "ledger_parse_failures_total{{cause=\"{cause}\"}} {count}"
+            );
+        }
+
+        let latency = &self.metrics.latency;
+        header(
+            &mut out,
+            "ledger_transaction_duration_seconds",
+            "histogram",
+            "Time spent handling a transaction.",
+        );
+        let mut cumulative = 0;
+        for (le, count) in LATENCY_BUCKETS.iter().zip(latency.buckets) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "ledger_transaction_duration_seconds_bucket{{le=\"{le}\"}} {cumulative}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "ledger_transaction_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            latency.count
+        );
+        let _ = writeln!(
+            out,
+            "ledger_transaction_duration_seconds_sum {}",
+            latency.sum
+        );
+        let _ = writeln!(
+            out,
+            "ledger_transaction_duration_seconds_count {}",
+            latency.count
+        );
+
+        let frozen = self
+            .accounts
+            .values()
+            .filter(|x| matches!(x.account.status, AccountStatus::Frozen))
+            .count();
+        let evicted = self.evicted;
+        let evicted = (evicted.deposits + evicted.withdrawals + evicted.transfers) as u64;
+        let gauges = [
+            (
+                "ledger_accounts",
+                "Client accounts.",
+                self.accounts.len() as u64,
+            ),
+            (
+                "ledger_frozen_accounts",
+                "Frozen client accounts.",
+                frozen as u64,
+            ),
+            (
+                "ledger_index_records",
+                "Records in the transaction index.",
+                stats.indexed.saturating_sub(evicted),
+            ),
+            (
+                "ledger_pending_reviews",
+                "Transactions waiting for review.",
+                self.pending.len() as u64,
+            ),
+            (
+                "ledger_parked_settlements",
+                "Settlements waiting for the transaction they reference.",
+                self.parked().count() as u64,
+            ),
+        ];
+        for (name, help, value) in gauges {
+            header(&mut out, name, "gauge", help);
+            let _ = writeln!(out, "{name} {value}");
+        }
+        out
+    }
+}
+
+/// Serves the latest published metrics over HTTP from a background thread.
+pub struct Exporter {
+    snapshot: Arc<Mutex<String>>,
+    addr: SocketAddr,
+}
+
+impl Exporter {
+    /// Binds `addr` and answers `GET /metrics`, until the process exits.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let snapshot = Arc::new(Mutex::new(String::new()));
+        let shared = Arc::clone(&snapshot);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // every connection gets its own thread, a stalled client can't hold up the rest
+                let shared = Arc::clone(&shared);
+                // a client going away mid-request isn't our problem
+                thread::spawn(move || respond(stream, &shared));
+            }
+        });
+        Ok(Self { snapshot, addr })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Replaces the served metrics with the current ones of `ledger`.
+    pub fn publish(&self, ledger: &Ledger) {
+        let rendered = ledger.render_metrics();
+        *self.snapshot.lock().unwrap_or_else(|x| x.into_inner()) = rendered;
+    }
+}
+
+/// Time left until `deadline`, a timed out error once it passed.
+fn left(deadline: Instant) -> io::Result<Duration> {
+    let left = deadline.saturating_duration_since(Instant::now());
+    if left.is_zero() {
+        return Err(io::ErrorKind::TimedOut.into());
+    }
+    Ok(left)
+}
+
+fn respond(mut stream: TcpStream, snapshot: &Mutex<String>) -> io::Result<()> {
+    // a client sending a byte at a time only gets `CLIENT_TIMEOUT` in total, not per read
+    let deadline = Instant::now() + CLIENT_TIMEOUT;
+    // only the request line matters, read until the end of the headers
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|x| x == b"\r\n\r\n") && request.len() < 8192 {
+        stream.set_read_timeout(Some(left(deadline)?))?;
+        let read = stream.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let (status, body) = if request.starts_with("GET /metrics ") {
+        let body = snapshot.lock().unwrap_or_else(|x| x.into_inner()).clone();
+        ("200 OK", body)
+    } else {
+        ("404 Not Found", String::new())
+    };
+    stream.set_write_timeout(Some(left(deadline)?))?;
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        time::Instant,
+    };
+
+    use crate::{
+        core::{
+            ledger::Ledger,
+            transaction::{NormalTransaction, Transaction},
+        },
+        Amount, Currency,
+    };
+
+    use super::{Exporter, CLIENT_TIMEOUT};
+
+    fn scrape(exporter: &Exporter, path: &str) -> String {
+        let mut stream = TcpStream::connect(exporter.local_addr()).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn scrape_local_endpoint() {
+        let mut ledger = Ledger::default();
+        let exporter = Exporter::bind("127.0.0.1:0").unwrap();
+        for (tx_id, client_id) in [(1, 1), (1, 1), (2, 2)] {
+            let _ = ledger.handle_transaction(&Transaction::Normal {
+                client_id,
+                tx_id,
+                kind: NormalTransaction::Deposit {
+                    amount: Amount::from_num(5),
+                    currency: Currency::default(),
+                },
+                timestamp: None,
+            });
+        }
+        exporter.publish(&ledger);
+
+        let response = scrape(&exporter, "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        for line in [
+            "ledger_transactions_total{kind=\"deposit\",outcome=\"applied\"} 2",
+            "ledger_transactions_total{kind=\"deposit\",outcome=\"ignored\"} 1",
+            "ledger_ignored_total{reason=\"duplicate_tx\"} 1",
+            "ledger_transaction_duration_seconds_count 3",
+            "ledger_accounts 2",
+            "ledger_index_records 2",
+        ] {
+            assert!(response.lines().any(|x| x == line), "missing {line}");
+        }
+        assert!(scrape(&exporter, "/").starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn silent_client_doesnt_stall_scrapes() {
+        let exporter = Exporter::bind("127.0.0.1:0").unwrap();
+        exporter.publish(&Ledger::default());
+        // connects but never sends a request
+        let _silent = TcpStream::connect(exporter.local_addr()).unwrap();
+        let start = Instant::now();
+        let response = scrape(&exporter, "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.lines().any(|x| x == "ledger_accounts 0"));
+        assert!(start.elapsed() < CLIENT_TIMEOUT);
+    }
+}
@@ -1,3 +1,19 @@
 pub mod account;
+pub mod events;
+pub mod exposure;
+pub mod fees;
+pub mod fraud;
+pub mod index;
+pub mod interest;
 pub mod ledger;
+pub mod lifecycle;
+pub mod limits;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod parking;
+pub mod projection;
+pub mod retention;
+pub mod review;
+pub mod stats;
 pub mod transaction;
+pub mod verify;
@@ -0,0 +1,293 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::{
+    ledger::{Errors, Ledger, Result},
+    retention::{DisputeWindow, RetentionPolicy},
+    stats::{Ignored, RecordKind, Skipped},
+    transaction::SettlementTransaction,
+};
+use crate::{Amount, ClientId, Timestamp, TxId};
+
+/// Settlements referencing a transaction the ledger hasn't seen yet are parked and applied
+/// once it arrives. The default policy doesn't park anything, such settlements are ignored.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParkingPolicy {
+    /// most settlements parked at once, the oldest one is dropped to make room.
+    /// as many dropped ones are kept for [`Ledger::unmatched`], older ones are only counted
+    pub capacity: usize,
+    /// parked settlements are dropped once this passes, `None` keeps them until the end
+    pub expiry: Option<DisputeWindow>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParkedSettlement {
+    pub client: ClientId,
+    pub tx: TxId,
+    pub kind: SettlementTransaction,
+    pub amount: Option<Amount>,
+    /// ledger sequence when it was parked
+    pub seq: u64,
+    /// latest ledger timestamp when it was parked
+    pub timestamp: Option<Timestamp>,
+}
+
+/// Why a parked settlement never matched a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unmatched {
+    /// parked for longer than [`ParkingPolicy::expiry`]
+    Expired,
+    /// dropped to make room for a newer one
+    Overflow,
+    /// still parked
+    Pending,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct Parking {
+    /// in arrival order
+    queue: VecDeque<ParkedSettlement>,
+    /// parked settlements per referenced transaction, so deposits only scan the queue
+    /// when something waits for them
+    waiting: BTreeMap<(ClientId, TxId), usize>,
+    /// the latest dropped settlements, at most [`ParkingPolicy::capacity`]
+    dropped: VecDeque<(ParkedSettlement, Unmatched)>,
+    /// every dropped settlement, including the ones no longer in `dropped`
+    dropped_total: usize,
+}
+
+impl Parking {
+    fn pop_front(&mut self, reason: Unmatched, capacity: usize) {
+        if let Some(parked) = self.queue.pop_front() {
+            debug!(?parked, ?reason, "dropped parked settlement");
+            self.forget(parked.client, parked.tx);
+            if self.dropped.len() >= capacity {
+                self.dropped.pop_front();
+            }
+            self.dropped.push_back((parked, reason));
+            self.dropped_total += 1;
+        }
+    }
+
+    fn forget(&mut self, client: ClientId, tx: TxId) {
+        if let Some(count) = self.waiting.get_mut(&(client, tx)) {
+            *count -= 1;
+            if *count == 0 {
+                self.waiting.remove(&(client, tx));
+            }
+        }
+    }
+}
+
+impl Ledger {
+    pub fn with_parking(mut self, parking: ParkingPolicy) -> Self {
+        self.parking_policy = parking;
+        self
+    }
+
+    pub(super) fn park(
+        &mut self,
+        client: ClientId,
+        tx: TxId,
+        kind: SettlementTransaction,
+        amount: Option<Amount>,
+    ) {
+        let capacity = self.parking_policy.capacity;
+        if capacity == 0 {
+            self.skip(Ignored::UnknownTx);
+            return;
+        }
+        debug!(parked = self.parking.queue.len() + 1, "parking settlement");
+        self.skipped = Some(Skipped::Deferred);
+        if self.parking.queue.len() >= capacity {
+            self.parking.pop_front(Unmatched::Overflow, capacity);
+        }
+        self.parking.queue.push_back(ParkedSettlement {
+            client,
+            tx,
+            kind,
+            amount,
+            seq: self.sequence,
+            timestamp: self.clock,
+        });
+        *self.parking.waiting.entry((client, tx)).or_default() += 1;
+    }
+
+    /// Drops parked settlements past the expiry, oldest first.
+    pub(super) fn expire_parked(&mut self) {
+        let expiry = RetentionPolicy {
+            dispute_window: self.parking_policy.expiry,
+            ..Default::default()
+        };
+        let now = (self.sequence, self.clock);
+        while self
+            .parking
+            .queue
+            .front()
+            .is_some_and(|x| expiry.expired((x.seq, x.timestamp), now))
+        {
+            self.parking
+                .pop_front(Unmatched::Expired, self.parking_policy.capacity);
+        }
+    }
+
+    /// Applies the settlements waiting for `(client, tx)` in the order they arrived.
+    /// Their rejections are dropped like the ones of settlements arriving in order,
+    /// the stats count them as if they arrived now.
+    pub(super) fn replay_parked(&mut self, client: ClientId, tx: TxId) -> Result {
+        if self.parking.waiting.remove(&(client, tx)).is_none() {
+            return Ok(());
+        }
+        let (matched, rest) = std::mem::take(&mut self.parking.queue)
+            .into_iter()
+            .partition(|x| (x.client, x.tx) == (client, tx));
+        self.parking.queue = rest;
+        let matched: VecDeque<ParkedSettlement> = matched;
+        for parked in matched {
+            debug!(?parked, "replaying parked settlement");
+            let result =
+                self.handle_settlement_transaction(client, tx, &parked.kind, parked.amount);
+            self.tally(RecordKind::from(&parked.kind), &result);
+            if let Err(Errors::Index(err)) = result {
+                return Err(Errors::Index(err));
+            }
+        }
+        Ok(())
+    }
+
+    /// Settlements that are still parked.
+    pub fn parked(&self) -> impl Iterator<Item = &ParkedSettlement> {
+        self.parking.queue.iter()
+    }
+
+    /// Settlements that never matched a transaction: the latest dropped ones (see
+    /// [`ParkingPolicy::capacity`]) followed by the ones still parked.
+    pub fn unmatched(&self) -> impl Iterator<Item = (&ParkedSettlement, Unmatched)> {
+        self.parking
+            .dropped
+            .iter()
+            .map(|(parked, reason)| (parked, *reason))
+            .chain(self.parked().map(|parked| (parked, Unmatched::Pending)))
+    }
+
+    /// Dropped settlements that are no longer listed by [`Ledger::unmatched`].
+    pub fn forgotten_unmatched(&self) -> usize {
+        self.parking.dropped_total - self.parking.dropped.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            ledger::Ledger,
+            retention::DisputeWindow,
+            transaction::{NormalTransaction, SettlementTransaction, Transaction},
+        },
+        Amount, ClientId, Currency, TxId,
+    };
+
+    use super::{ParkingPolicy, Unmatched};
+
+    fn deposit(client_id: ClientId, tx_id: TxId, amount: i32) -> Transaction {
+        Transaction::Normal {
+            client_id,
+            tx_id,
+            kind: NormalTransaction::Deposit {
+                amount: Amount::from_num(amount),
+                currency: Currency::default(),
+            },
+            timestamp: None,
+        }
+    }
+
+    fn settle(client_id: ClientId, tx_id: TxId, kind: SettlementTransaction) -> Transaction {
+        Transaction::SettlementTransaction {
+            client_id,
+            tx_id,
+            kind,
+            amount: None,
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn replays_when_the_deposit_arrives() {
+        let mut ledger = Ledger::default().with_parking(ParkingPolicy {
+            capacity: 3,
+            expiry: Some(DisputeWindow::Transactions(4)),
+        });
+        let txs = [
+            settle(1, 1, SettlementTransaction::Dispute),
+            settle(1, 1, SettlementTransaction::Chargeback),
+            settle(2, 5, SettlementTransaction::Dispute),
+            // pushes the first one out
+            settle(3, 6, SettlementTransaction::Dispute),
+            deposit(1, 1, 10),
+            deposit(1, 2, 10),
+            settle(3, 7, SettlementTransaction::Dispute),
+        ];
+        for tx in &txs {
+            let _ = ledger.handle_transaction(tx);
+        }
+        // only the chargeback was left, there was nothing to charge back
+        let account = ledger.get_account(&1).unwrap();
+        assert_eq!(account.total(&Currency::default()), Amount::from_num(20));
+        assert!(ledger.verify().unwrap().is_ok());
+
+        // parked for more than 4 transactions
+        for tx_id in 3..6 {
+            ledger.handle_transaction(&deposit(1, tx_id, 1)).unwrap();
+        }
+        let unmatched: Vec<_> = ledger
+            .unmatched()
+            .map(|(parked, reason)| (parked.client, parked.tx, reason))
+            .collect();
+        assert_eq!(
+            unmatched,
+            vec![
+                (1, 1, Unmatched::Overflow),
+                (2, 5, Unmatched::Expired),
+                (3, 6, Unmatched::Expired),
+                (3, 7, Unmatched::Pending),
+            ]
+        );
+        ledger.handle_transaction(&deposit(3, 7, 5)).unwrap();
+        assert_eq!(ledger.parked().count(), 0);
+        let held = ledger.get_account(&3).unwrap().wallets[&Currency::default()]
+            .held
+            .amount;
+        assert_eq!(held, Amount::from_num(5));
+        assert!(ledger.verify().unwrap().is_ok());
+    }
+
+    #[test]
+    fn keeps_the_latest_dropped() {
+        let mut ledger = Ledger::default().with_parking(ParkingPolicy {
+            capacity: 2,
+            expiry: None,
+        });
+        for tx_id in 1..11 {
+            ledger
+                .handle_transaction(&settle(1, tx_id, SettlementTransaction::Dispute))
+                .unwrap();
+        }
+        let unmatched: Vec<_> = ledger
+            .unmatched()
+            .map(|(parked, reason)| (parked.tx, reason))
+            .collect();
+        assert_eq!(
+            unmatched,
+            vec![
+                (7, Unmatched::Overflow),
+                (8, Unmatched::Overflow),
+                (9, Unmatched::Pending),
+                (10, Unmatched::Pending),
+            ]
+        );
+        assert_eq!(ledger.forgotten_unmatched(), 6);
+    }
+}
@@ -0,0 +1,333 @@
+//! Rebuilds the balances of a ledger from its [`LedgerEvent`]s alone.
+//!
+//! The events record what happened to the balances, not the transactions that caused it,
+//! so a projection doesn't depend on the validation rules of [`Ledger::handle_transaction`]:
+//! changing them later doesn't alter the projected history. The chargeback and dispute counts
+//! of the clients are rebuilt as well, the input [`Ledger::stats`] aren't.
+
+use std::{
+    io::{self, BufReader, Read},
+    sync::{Arc, Mutex},
+};
+
+use super::{
+    account::AccountStatus,
+    events::{Event, LedgerEvent, Subscriber},
+    ledger::Ledger,
+};
+
+/// Keeps every event of a ledger in memory. Clones share the log, so one clone can be
+/// registered with [`Ledger::with_subscriber`] while the other reads it.
+#[derive(Debug, Clone, Default)]
+pub struct EventLog(Arc<Mutex<Vec<LedgerEvent>>>);
+
+impl EventLog {
+    pub fn events(&self) -> Vec<LedgerEvent> {
+        self.0.lock().unwrap_or_else(|x| x.into_inner()).clone()
+    }
+}
+
+impl Subscriber for EventLog {
+    fn notify(&mut self, event: &LedgerEvent) {
+        self.0
+            .lock()
+            .unwrap_or_else(|x| x.into_inner())
+            .push(*event);
+    }
+}
+
+/// Reads a log written by [`super::events::JsonLines`].
+pub fn read_events<R: Read>(input: R) -> impl Iterator<Item = io::Result<LedgerEvent>> {
+    serde_json::Deserializer::from_reader(BufReader::new(input))
+        .into_iter()
+        .map(|x| x.map_err(io::Error::from))
+}
+
+impl Ledger {
+    /// Folds `events` into an empty ledger. The projection has the balances, statuses, liabilities,
+    /// house balances and chargeback counts of the ledger that emitted them but no transaction
+    /// history, so it's meant for reading: settlements handled by it find nothing to dispute and
+    /// [`Ledger::verify`] can't match held and reserved funds to disputes and reviews.
+    pub fn from_events(events: impl IntoIterator<Item = LedgerEvent>) -> Self {
+        let mut ledger = Ledger {
+            projected: true,
+            ..Default::default()
+        };
+        for event in events {
+            ledger.apply_event(&event);
+        }
+        ledger
+    }
+
+    /// Applies the balance change of a single event, the same way the ledger made it.
+    pub fn apply_event(&mut self, event: &LedgerEvent) {
+        self.sequence = self.sequence.max(event.seq);
+        self.clock = self.clock.max(event.timestamp);
+        match event.event {
+            Event::AccountOpened { client } => {
+                self.accounts.entry(client).or_default();
+            }
+            Event::FundsDeposited {
+                client,
+                currency,
+                amount,
+            } => {
+                let liabilities = self.liabilites.entry(currency).or_default();
+                let client = self.accounts.entry(client).or_default();
+                client.account.deposit(liabilities, currency, amount);
+            }
+            Event::FundsWithdrawn {
+                client,
+                currency,
+                amount,
+            } => {
+                let liabilities = self.liabilites.entry(currency).or_default();
+                let client = self.accounts.entry(client).or_default();
+                client.account.debit(liabilities, currency, amount);
+            }
+            Event::FundsHeld {
+                client,
+                currency,
+                amount,
+                sender,
+            } => {
+                self.accounts
+                    .entry(client)
+                    .or_default()
+                    .account
+                    .hold(currency, amount);
+                // disputes count for the client that opened them
+                self.accounts
+                    .entry(sender.unwrap_or(client))
+                    .or_default()
+                    .disputes += 1;
+            }
+            Event::FundsReleased {
+                client,
+                currency,
+                amount,
+            } => {
+                let client = self.accounts.entry(client).or_default();
+                client.account.resolve(currency, amount);
+            }
+            Event::ChargedBack {
+                client,
+                currency,
+                amount,
+                sender,
+                first,
+            } => {
+                // a returned transfer goes through the liabilities with the deposit at the sender
+                let liabilities = self.liabilites.entry(currency).or_default();
+                let client = self.accounts.entry(client).or_default();
+                client.account.chargeback(liabilities, currency, amount);
+                *client.charged_back.entry(currency).or_default() += amount;
+                if first {
+                    client.chargebacks += 1;
+                }
+                if sender.is_none() {
+                    *self.charged_back.entry(currency).or_default() += amount;
+                }
+            }
+            Event::ChargebackReversed {
+                client,
+                currency,
+                amount,
+                sender,
+                complete,
+            } => {
+                let liabilities = self.liabilites.entry(currency).or_default();
+                let client = self.accounts.entry(client).or_default();
+                client.account.deposit(liabilities, currency, amount);
+                *client.charged_back.entry(currency).or_default() -= amount;
+                if complete {
+                    client.chargebacks = client.chargebacks.saturating_sub(1);
+                    client.reversals += 1;
+                }
+                if sender.is_none() {
+                    *self.charged_back.entry(currency).or_default() -= amount;
+                }
+            }
+            Event::AccountFrozen { client } => {
+                self.accounts.entry(client).or_default().account.status = AccountStatus::Frozen;
+            }
+            Event::AccountUnfrozen { client } => {
+                self.accounts.entry(client).or_default().account.status = AccountStatus::Active;
+            }
+            Event::FeeCharged {
+                client,
+                currency,
+                amount,
+            } => {
+                let revenue = self.revenue.entry(currency).or_default();
+                let client = self.accounts.entry(client).or_default();
+                client.account.pay_fee(revenue, currency, amount);
+            }
+            Event::InterestPosted {
+                client,
+                currency,
+                amount,
+            } => {
+                let house = self.interest_house.entry(currency).or_default();
+                let client = self.accounts.entry(client).or_default();
+                client.account.post_interest(house, currency, amount);
+            }
+            Event::FundsReserved {
+                client,
+                currency,
+                amount,
+            } => {
+                let client = self.accounts.entry(client).or_default();
+                client.account.reserve(currency, amount);
+            }
+            Event::ReservationReleased {
+                client,
+                currency,
+                amount,
+            } => {
+                let client = self.accounts.entry(client).or_default();
+                client.account.release(currency, amount);
+            }
+            Event::DepositPending {
+                client,
+                currency,
+                amount,
+            } => {
+                let client = self.accounts.entry(client).or_default();
+                client.account.expect_deposit(currency, amount);
+            }
+            Event::DepositReviewed {
+                client,
+                currency,
+                amount,
+            } => {
+                let client = self.accounts.entry(client).or_default();
+                client.account.expect_deposit(currency, -amount);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            ledger::Ledger,
+            lifecycle::{DisputeLifecycle, UnfreezePolicy},
+            transaction::{NormalTransaction, SettlementTransaction, Transaction},
+        },
+        Amount, ClientId, Currency, TxId,
+    };
+
+    use super::EventLog;
+
+    fn normal(client_id: ClientId, tx_id: TxId, kind: NormalTransaction) -> Transaction {
+        Transaction::Normal {
+            client_id,
+            tx_id,
+            kind,
+            timestamp: None,
+        }
+    }
+
+    fn settle(client_id: ClientId, tx_id: TxId, kind: SettlementTransaction) -> Transaction {
+        settle_part(client_id, tx_id, kind, None)
+    }
+
+    fn settle_part(
+        client_id: ClientId,
+        tx_id: TxId,
+        kind: SettlementTransaction,
+        amount: Option<Amount>,
+    ) -> Transaction {
+        Transaction::SettlementTransaction {
+            client_id,
+            tx_id,
+            kind,
+            amount,
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn folds_transfers_and_reversals() {
+        let log = EventLog::default();
+        let mut ledger = Ledger::default()
+            .with_lifecycle(DisputeLifecycle::default().with_unfreeze(UnfreezePolicy::WhenReversed))
+            .with_subscriber(log.clone());
+        let currency = Currency::default();
+        let euro: Currency = "EUR".parse().unwrap();
+        let amount = Amount::from_num(10);
+        let half = amount / 2;
+        let txs = [
+            normal(1, 1, NormalTransaction::Deposit { amount, currency }),
+            normal(1, 2, NormalTransaction::Deposit { amount, currency }),
+            normal(
+                1,
+                3,
+                NormalTransaction::Transfer {
+                    amount,
+                    currency,
+                    to: 2,
+                },
+            ),
+            settle(1, 3, SettlementTransaction::Dispute),
+            settle(1, 3, SettlementTransaction::Chargeback),
+            settle(1, 3, SettlementTransaction::Reversal),
+            settle(1, 1, SettlementTransaction::Dispute),
+            settle(1, 1, SettlementTransaction::Chargeback),
+            settle(1, 1, SettlementTransaction::Reversal),
+            settle(1, 1, SettlementTransaction::Dispute),
+            // partly charged back and reversed in another currency
+            normal(
+                3,
+                4,
+                NormalTransaction::Deposit {
+                    amount,
+                    currency: euro,
+                },
+            ),
+            settle(3, 4, SettlementTransaction::Dispute),
+            settle_part(3, 4, SettlementTransaction::Chargeback, Some(half)),
+            settle_part(3, 4, SettlementTransaction::Resolve, Some(half)),
+            settle_part(3, 4, SettlementTransaction::Reversal, Some(half / 2)),
+        ];
+        for tx in &txs {
+            let _ = ledger.handle_transaction(tx);
+        }
+        let projected = Ledger::from_events(log.events());
+        assert_eq!(
+            projected.rows().collect::<Vec<_>>(),
+            ledger.rows().collect::<Vec<_>>()
+        );
+        for currency in [currency, euro] {
+            assert_eq!(
+                projected.liabilities(&currency),
+                ledger.liabilities(&currency)
+            );
+        }
+        assert_eq!(
+            projected.account_exposure().collect::<Vec<_>>(),
+            ledger.account_exposure().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            projected.liability_exposure().collect::<Vec<_>>(),
+            ledger.liability_exposure().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            projected.summary().charged_back,
+            ledger.summary().charged_back
+        );
+        assert!(projected.verify().unwrap().is_ok());
+        assert_eq!(projected.sequence(), ledger.sequence());
+        // the projection has no history to dispute
+        let mut projected = projected;
+        let _ = projected.handle_transaction(&settle(1, 2, SettlementTransaction::Dispute));
+        assert_eq!(
+            projected.get_account(&1).unwrap().wallets[&currency]
+                .held
+                .amount,
+            amount
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use std::{fmt, io, mem::size_of, num::NonZeroU64, ops::AddAssign};
+
+use chrono::TimeDelta;
+use tracing::debug;
+
+use super::{
+    index::{Tombstone, TxRecord, TxSpace},
+    ledger::{Ledger, TransactionOutcomeKind},
+};
+use crate::{ClientId, Timestamp, TxId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisputeWindow {
+    /// a deposit can be disputed until `n` more transactions were handled by the ledger
+    Transactions(u64),
+    /// a deposit can be disputed for this long after its timestamp, measured against the latest
+    /// timestamp seen by the ledger. deposits without a timestamp never expire
+    Duration(TimeDelta),
+}
+
+/// Controls how long the ledger keeps deposit/withdrawal history around.
+/// Evicted records leave a [`Tombstone`] in the index: disputes of expired ones fail with
+/// [`super::ledger::Errors::DisputeWindowExpired`], settlements of settled ones are ignored as
+/// invalid transitions and a replayed transaction with the same id is ignored as a duplicate.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RetentionPolicy {
+    /// `None` keeps every deposit disputable forever
+    pub dispute_window: Option<DisputeWindow>,
+    /// drop resolved and charged back deposits, they can't be disputed anymore
+    pub evict_settled: bool,
+    /// run [`Ledger::evict`] automatically every `n` transactions
+    pub sweep_every: Option<NonZeroU64>,
+}
+
+impl RetentionPolicy {
+    /// both arguments are `(sequence, timestamp)` pairs, of the record and of the ledger
+    pub(super) fn expired(
+        &self,
+        (seq, timestamp): (u64, Option<Timestamp>),
+        (now_seq, now): (u64, Option<Timestamp>),
+    ) -> bool {
+        match self.dispute_window {
+            Some(DisputeWindow::Transactions(window)) => now_seq.saturating_sub(seq) > window,
+            Some(DisputeWindow::Duration(window)) => match (timestamp, now) {
+                (Some(timestamp), Some(now)) => now - timestamp > window,
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    pub(super) fn sweep_due(&self, now: u64) -> bool {
+        self.sweep_every
+            .is_some_and(|every| now.is_multiple_of(every.get()))
+    }
+}
+
+/// Records dropped by [`Ledger::evict`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Eviction {
+    pub deposits: usize,
+    pub withdrawals: usize,
+    pub transfers: usize,
+}
+
+impl Eviction {
+    /// Estimate of the freed memory, counts only the record payloads less the tombstones left in
+    /// their place and not the index overhead.
+    pub fn reclaimed_bytes(&self) -> usize {
+        let reclaimed = size_of::<TxRecord>() - size_of::<Tombstone>();
+        (self.deposits + self.withdrawals + self.transfers) * reclaimed
+    }
+}
+
+impl AddAssign for Eviction {
+    fn add_assign(&mut self, rhs: Self) {
+        self.deposits += rhs.deposits;
+        self.withdrawals += rhs.withdrawals;
+        self.transfers += rhs.transfers;
+    }
+}
+
+impl fmt::Display for Eviction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "evicted {} deposits, {} withdrawals and {} transfers, reclaimed ~{} bytes",
+            self.deposits,
+            self.withdrawals,
+            self.transfers,
+            self.reclaimed_bytes()
+        )
+    }
+}
+
+impl Ledger {
+    /// Drops every record that can't be disputed anymore according to the retention policy:
+    /// deposits, transfers and withdrawals past the dispute window and (if enabled) settled
+    /// deposits and transfers. Disputed ones are always kept as they still hold funds.
+    /// Evicted records leave a [`Tombstone`] behind, their ids stay taken.
+    pub fn evict(&mut self) -> io::Result<Eviction> {
+        let policy = self.retention;
+        let now = (self.sequence, self.clock);
+        let mut eviction = Eviction::default();
+        self.index.bury(&mut |_, _, record| {
+            let expired = |seq, timestamp| {
+                policy
+                    .expired((seq, timestamp), now)
+                    .then_some(Tombstone::Expired)
+            };
+            let tombstone = match record {
+                TxRecord::Deposit(deposit)
+                | TxRecord::Transfer {
+                    outcome: deposit, ..
+                } => match deposit.kind {
+                    TransactionOutcomeKind::Applied => expired(deposit.seq, deposit.timestamp),
+                    TransactionOutcomeKind::Disputed | TransactionOutcomeKind::PreArbitration => {
+                        None
+                    }
+                    TransactionOutcomeKind::Resolved
+                    | TransactionOutcomeKind::Chargeback
+                    | TransactionOutcomeKind::ChargebackReversed
+                    | TransactionOutcomeKind::SecondChargeback => {
+                        policy.evict_settled.then_some(Tombstone::Settled)
+                    }
+                },
+                TxRecord::Withdrawal { seq, timestamp, .. } => expired(*seq, *timestamp),
+            };
+            if tombstone.is_some() {
+                match record {
+                    TxRecord::Deposit(_) => eviction.deposits += 1,
+                    TxRecord::Withdrawal { .. } => eviction.withdrawals += 1,
+                    TxRecord::Transfer { .. } => eviction.transfers += 1,
+                }
+            }
+            tombstone
+        })?;
+        debug!(?eviction, "evicted history");
+        self.evicted += eviction;
+        Ok(eviction)
+    }
+
+    /// Whether the client already used `tx_id` in `space`: for a transaction waiting for review,
+    /// an applied one or one whose record was dropped by [`Ledger::evict`].
+    pub(super) fn is_known_tx(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        space: TxSpace,
+    ) -> io::Result<bool> {
+        let pending = self.pending.get(&(client_id, tx_id));
+        Ok(
+            pending.is_some_and(|x| TxSpace::from(&x.transaction) == space)
+                || self.index.get(client_id, tx_id, space)?.is_some()
+                || self.index.tombstone(client_id, tx_id, space)?.is_some(),
+        )
+    }
+
+    /// Totals of every eviction so far.
+    pub fn evicted(&self) -> Eviction {
+        self.evicted
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU64;
+
+    use chrono::TimeDelta;
+
+    use crate::{
+        core::{
+            ledger::{Errors, Ledger},
+            stats::Ignored,
+            transaction::{NormalTransaction, SettlementTransaction, Transaction},
+        },
+        Amount, ClientId, Currency, TxId,
+    };
+
+    use super::{DisputeWindow, Eviction, RetentionPolicy};
+
+    fn deposit(client_id: ClientId, tx_id: TxId) -> Transaction {
+        Transaction::Normal {
+            client_id,
+            tx_id,
+            kind: NormalTransaction::Deposit {
+                amount: Amount::ONE,
+                currency: Currency::default(),
+            },
+            timestamp: None,
+        }
+    }
+    fn settle(client_id: ClientId, tx_id: TxId, kind: SettlementTransaction) -> Transaction {
+        Transaction::SettlementTransaction {
+            client_id,
+            tx_id,
+            kind,
+            amount: None,
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn dispute_past_window() {
+        let mut ledger = Ledger::default().with_retention(RetentionPolicy {
+            dispute_window: Some(DisputeWindow::Transactions(2)),
+            ..Default::default()
+        });
+        ledger.handle_transaction(&deposit(1, 1)).unwrap();
+        ledger.handle_transaction(&deposit(1, 2)).unwrap();
+        ledger.handle_transaction(&deposit(1, 3)).unwrap();
+        ledger
+            .handle_transaction(&settle(1, 2, SettlementTransaction::Dispute))
+            .unwrap();
+        let expired = ledger.handle_transaction(&settle(1, 1, SettlementTransaction::Dispute));
+        assert!(matches!(expired, Err(Errors::DisputeWindowExpired(1))));
+        assert_eq!(
+            ledger.get_account(&1).unwrap().wallets[&Currency::default()]
+                .held
+                .amount,
+            Amount::ONE
+        );
+    }
+
+    #[test]
+    fn dispute_past_duration_window() {
+        let mut ledger = Ledger::default().with_retention(RetentionPolicy {
+            dispute_window: Some(DisputeWindow::Duration(TimeDelta::days(120))),
+            ..Default::default()
+        });
+        let at = |day: u32| Some(format!("2024-{day:02}-01T00:00:00Z").parse().unwrap());
+        let txs = [
+            (deposit(1, 1), at(1)),
+            (deposit(1, 2), None),
+            (deposit(1, 3), at(3)),
+            (settle(1, 3, SettlementTransaction::Dispute), at(6)),
+        ];
+        for (mut tx, timestamp) in txs {
+            match &mut tx {
+                Transaction::Normal { timestamp: ts, .. }
+                | Transaction::SettlementTransaction { timestamp: ts, .. }
+                | Transaction::Review { timestamp: ts, .. }
+                | Transaction::Control { timestamp: ts, .. } => *ts = timestamp,
+            }
+            ledger.handle_transaction(&tx).unwrap();
+        }
+        let expired = ledger.handle_transaction(&settle(1, 1, SettlementTransaction::Dispute));
+        assert!(matches!(expired, Err(Errors::DisputeWindowExpired(1))));
+        // deposits without a timestamp never expire
+        ledger
+            .handle_transaction(&settle(1, 2, SettlementTransaction::Dispute))
+            .unwrap();
+        assert_eq!(ledger.clock(), at(6));
+        assert_eq!(
+            ledger.get_account(&1).unwrap().wallets[&Currency::default()]
+                .held
+                .amount,
+            Amount::from_num(2)
+        );
+    }
+
+    #[test]
+    fn evicts_settled_and_expired() {
+        let mut ledger = Ledger::default().with_retention(RetentionPolicy {
+            dispute_window: Some(DisputeWindow::Transactions(3)),
+            evict_settled: true,
+            sweep_every: NonZeroU64::new(11),
+        });
+        let txs = [
+            deposit(1, 1),
+            deposit(1, 2),
+            deposit(1, 3),
+            settle(1, 1, SettlementTransaction::Dispute),
+            settle(1, 2, SettlementTransaction::Dispute),
+            settle(1, 2, SettlementTransaction::Resolve),
+            deposit(1, 4),
+        ];
+        for tx in &txs {
+            ledger.handle_transaction(tx).unwrap();
+        }
+        // 1 is still disputed, 2 is resolved, 3 expired and 4 is recent
+        let eviction = ledger.evict().unwrap();
+        assert_eq!(
+            eviction,
+            Eviction {
+                deposits: 2,
+                withdrawals: 0,
+                transfers: 0
+            }
+        );
+        assert!(eviction.reclaimed_bytes() > 0);
+        assert!(ledger.verify().unwrap().is_ok());
+
+        for tx_id in 5..9 {
+            ledger.handle_transaction(&deposit(1, tx_id)).unwrap();
+        }
+        // automatic sweep on the 11th transaction drops 4
+        assert_eq!(ledger.evicted().deposits, 3);
+    }
+
+    #[test]
+    fn evicted_ids_stay_known() {
+        let mut ledger = Ledger::default().with_retention(RetentionPolicy {
+            dispute_window: Some(DisputeWindow::Transactions(1)),
+            ..Default::default()
+        });
+        for tx_id in 1..5 {
+            ledger.handle_transaction(&deposit(1, tx_id)).unwrap();
+        }
+        assert_eq!(ledger.evict().unwrap().deposits, 2);
+
+        let expired = ledger.handle_transaction(&settle(1, 1, SettlementTransaction::Dispute));
+        assert!(matches!(expired, Err(Errors::DisputeWindowExpired(1))));
+        // a replay isn't applied twice
+        ledger.handle_transaction(&deposit(1, 1)).unwrap();
+        assert_eq!(
+            ledger.get_account(&1).unwrap().wallets[&Currency::default()]
+                .available
+                .amount,
+            Amount::from_num(4)
+        );
+        assert!(ledger.verify().unwrap().is_ok());
+    }
+
+    #[test]
+    fn settled_evictions_arent_expired() {
+        let mut ledger = Ledger::default().with_retention(RetentionPolicy {
+            dispute_window: Some(DisputeWindow::Transactions(100)),
+            evict_settled: true,
+            ..Default::default()
+        });
+        let txs = [
+            deposit(1, 1),
+            settle(1, 1, SettlementTransaction::Dispute),
+            settle(1, 1, SettlementTransaction::Resolve),
+        ];
+        for tx in &txs {
+            ledger.handle_transaction(tx).unwrap();
+        }
+        assert_eq!(ledger.evict().unwrap().deposits, 1);
+        // still inside the window, but a resolved deposit can't be disputed again
+        ledger
+            .handle_transaction(&settle(1, 1, SettlementTransaction::Dispute))
+            .unwrap();
+        assert_eq!(ledger.stats().ignored[&Ignored::InvalidTransition], 1);
+        assert!(ledger.verify().unwrap().is_ok());
+    }
+}
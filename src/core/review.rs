@@ -0,0 +1,365 @@
+use std::collections::BTreeMap;
+
+use tracing::debug;
+
+use super::{
+    account::AccountStatus,
+    events::Event,
+    index::TxSpace,
+    ledger::{Ledger, Result},
+    stats::{Ignored, Skipped},
+    transaction::{NormalTransaction, ReviewTransaction},
+};
+use crate::{Amount, ClientId, Currency, Timestamp, TxId};
+
+/// A client transaction that scored at least [`super::fraud::FraudPolicy::review_score`].
+/// It isn't applied until an `approve` record, a `decline` record drops it.
+#[derive(Debug, Clone)]
+pub struct PendingTx {
+    pub transaction: NormalTransaction,
+    pub score: u32,
+    pub timestamp: Option<Timestamp>,
+    /// moved from `available` to `reserved` until the review, zero for deposits
+    pub reserved: Amount,
+    /// whether the account shows the funds as pending, a deposit of a client without an account
+    /// is only tracked here and opens the account once it's approved
+    pub on_account: bool,
+}
+
+impl PendingTx {
+    pub fn amount(&self) -> Amount {
+        match self.transaction {
+            NormalTransaction::Deposit { amount, .. }
+            | NormalTransaction::Withdraw { amount, .. }
+            | NormalTransaction::Transfer { amount, .. } => amount,
+        }
+    }
+    pub fn currency(&self) -> Currency {
+        match self.transaction {
+            NormalTransaction::Deposit { currency, .. }
+            | NormalTransaction::Withdraw { currency, .. }
+            | NormalTransaction::Transfer { currency, .. } => currency,
+        }
+    }
+}
+
+impl Ledger {
+    /// Transactions waiting for review, keyed by client and tx id.
+    pub fn pending(&self) -> &BTreeMap<(ClientId, TxId), PendingTx> {
+        &self.pending
+    }
+
+    /// Parks `tx` until it's approved or declined. Transactions the ledger would ignore
+    /// (frozen or unknown clients, reused tx ids, unfunded withdrawals) go through as usual.
+    /// Reviews name only the tx id, so a deposit and a withdrawal sharing one can't both wait.
+    pub(super) fn queue_for_review(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        tx: &NormalTransaction,
+        timestamp: Option<Timestamp>,
+        score: u32,
+    ) -> Result {
+        if self.pending.contains_key(&(client_id, tx_id)) {
+            self.skip(Ignored::DuplicateTx);
+            return Ok(());
+        }
+        let ignored = match self.accounts.get(&client_id) {
+            Some(x) if !matches!(x.account.status, AccountStatus::Active) => true,
+            Some(_) => self.is_known_tx(client_id, tx_id, TxSpace::from(tx))?,
+            None => false,
+        };
+        let client = self.accounts.get(&client_id);
+        let reserved = match *tx {
+            _ if ignored => None,
+            NormalTransaction::Deposit { .. } => Some(Amount::ZERO),
+            NormalTransaction::Withdraw { amount, .. } => Some(amount + self.fees.withdrawal),
+            NormalTransaction::Transfer { amount, to, .. } => {
+                let receiver_frozen = self
+                    .accounts
+                    .get(&to)
+                    .is_some_and(|x| !matches!(x.account.status, AccountStatus::Active));
+                (!receiver_frozen && to != client_id).then_some(amount)
+            }
+        };
+        let credit = self.limits.credit(client_id);
+        let pending = PendingTx {
+            transaction: tx.clone(),
+            score,
+            timestamp,
+            reserved: reserved.unwrap_or_default(),
+            on_account: client.is_some(),
+        };
+        let currency = pending.currency();
+        let funded = reserved.is_some_and(|reserved| {
+            reserved == Amount::ZERO
+                || client
+                    .and_then(|x| x.account.wallet(&currency))
+                    .is_some_and(|wallet| wallet.available.amount + credit >= reserved)
+        });
+        if !funded {
+            debug!(score, "not queued for review, the ledger would ignore it");
+            return self.handle_normal_transaction(client_id, tx_id, tx, timestamp, score);
+        }
+        debug!(score, reserved = %pending.reserved, "queued for review");
+        if let Some(client) = self.accounts.get_mut(&client_id) {
+            let account = &mut client.account;
+            let event = match pending.transaction {
+                NormalTransaction::Deposit { amount, .. } => {
+                    account.expect_deposit(currency, amount);
+                    Event::DepositPending {
+                        client: client_id,
+                        currency,
+                        amount,
+                    }
+                }
+                _ => {
+                    account.reserve(currency, pending.reserved);
+                    Event::FundsReserved {
+                        client: client_id,
+                        currency,
+                        amount: pending.reserved,
+                    }
+                }
+            };
+            self.emit(event);
+        }
+        self.pending.insert((client_id, tx_id), pending);
+        self.skipped = Some(Skipped::Deferred);
+        Ok(())
+    }
+
+    /// Approving applies the transaction as if it arrived now, so limits are checked
+    /// and fees charged at that point.
+    pub(super) fn handle_review(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TxId,
+        kind: &ReviewTransaction,
+    ) -> Result {
+        let Some(pending) = self.pending.remove(&(client_id, tx_id)) else {
+            self.skip(Ignored::UnknownTx);
+            return Ok(());
+        };
+        let currency = pending.currency();
+        let client = self.accounts.get_mut(&client_id);
+        if let Some(client) = client.filter(|_| pending.on_account) {
+            let event = match pending.transaction {
+                NormalTransaction::Deposit { amount, .. } => {
+                    client.account.expect_deposit(currency, -amount);
+                    Event::DepositReviewed {
+                        client: client_id,
+                        currency,
+                        amount,
+                    }
+                }
+                _ => {
+                    client.account.release(currency, pending.reserved);
+                    Event::ReservationReleased {
+                        client: client_id,
+                        currency,
+                        amount: pending.reserved,
+                    }
+                }
+            };
+            self.emit(event);
+        }
+        match kind {
+            ReviewTransaction::Approve => self.handle_normal_transaction(
+                client_id,
+                tx_id,
+                &pending.transaction,
+                pending.timestamp,
+                pending.score,
+            ),
+            ReviewTransaction::Decline => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            fraud::{ClientView, FraudCheck, FraudPolicy, Verdict},
+            ledger::Ledger,
+            transaction::{NormalTransaction, ReviewTransaction, Transaction},
+        },
+        Amount, ClientId, Currency, TxId,
+    };
+
+    fn amount(x: &str) -> Amount {
+        Amount::from_str(x).unwrap()
+    }
+
+    /// flags everything above 100
+    struct Large;
+
+    impl FraudCheck for Large {
+        fn check(&self, tx: &Transaction, _: Option<ClientView<'_>>) -> Verdict {
+            let score = match tx {
+                Transaction::Normal {
+                    kind:
+                        NormalTransaction::Deposit { amount: value, .. }
+                        | NormalTransaction::Withdraw { amount: value, .. }
+                        | NormalTransaction::Transfer { amount: value, .. },
+                    ..
+                } if *value > amount("100") => 50,
+                _ => 0,
+            };
+            Verdict::Allow { score }
+        }
+    }
+
+    fn normal(client_id: ClientId, tx_id: TxId, kind: NormalTransaction) -> Transaction {
+        Transaction::Normal {
+            client_id,
+            tx_id,
+            kind,
+            timestamp: None,
+        }
+    }
+
+    fn review(client_id: ClientId, tx_id: TxId, kind: ReviewTransaction) -> Transaction {
+        Transaction::Review {
+            client_id,
+            tx_id,
+            kind,
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn approve_and_decline() {
+        let mut ledger = Ledger::default().with_fraud(FraudPolicy {
+            checks: vec![Box::new(Large)],
+            reject_score: None,
+            review_score: Some(50),
+        });
+        let currency = Currency::default();
+        let deposit = |value| NormalTransaction::Deposit {
+            amount: amount(value),
+            currency,
+        };
+        let withdraw = |value| NormalTransaction::Withdraw {
+            amount: amount(value),
+            currency,
+        };
+        let txs = [
+            normal(1, 1, deposit("100")),
+            normal(1, 2, deposit("500")),
+            normal(1, 3, withdraw("60")),
+            // can't reserve more than available, ignored as usual
+            normal(1, 4, withdraw("150")),
+            normal(1, 5, withdraw("20")),
+        ];
+        for tx in &txs {
+            ledger.handle_transaction(tx).unwrap();
+        }
+        assert_eq!(ledger.pending().len(), 1);
+        let rows: Vec<_> = ledger.rows().collect();
+        assert_eq!(rows[0].available, amount("20"));
+        assert_eq!(rows[0].total, amount("20"));
+        assert_eq!(rows[0].pending_deposits, amount("500"));
+        assert!(ledger.verify().unwrap().is_ok());
+
+        ledger
+            .handle_transaction(&review(1, 2, ReviewTransaction::Approve))
+            .unwrap();
+        ledger
+            .handle_transaction(&normal(1, 6, withdraw("400")))
+            .unwrap();
+        assert_eq!(ledger.pending().len(), 1);
+        let pending = &ledger.pending()[&(1, 6)];
+        assert_eq!((pending.score, pending.reserved), (50, amount("400")));
+        let wallet = ledger.get_account(&1).unwrap().wallet(&currency).unwrap();
+        assert_eq!(wallet.available.amount, amount("120"));
+        assert_eq!(wallet.reserved.amount, amount("400"));
+        assert!(ledger.verify().unwrap().is_ok());
+
+        ledger
+            .handle_transaction(&review(1, 6, ReviewTransaction::Decline))
+            .unwrap();
+        // already reviewed
+        ledger
+            .handle_transaction(&review(1, 6, ReviewTransaction::Approve))
+            .unwrap();
+        assert!(ledger.pending().is_empty());
+        let total = ledger.get_account(&1).unwrap().total(&currency);
+        assert_eq!(total, amount("520"));
+        let history = ledger.history(1).unwrap();
+        assert_eq!(history.len(), 5);
+        assert_eq!(history[1].1.score(), 50);
+        assert!(ledger.verify().unwrap().is_ok());
+    }
+
+    #[test]
+    fn pending_tx_id_is_taken() {
+        let mut ledger = Ledger::default().with_fraud(FraudPolicy {
+            checks: vec![Box::new(Large)],
+            reject_score: None,
+            review_score: Some(50),
+        });
+        let currency = Currency::default();
+        let deposit = |value| NormalTransaction::Deposit {
+            amount: amount(value),
+            currency,
+        };
+        ledger
+            .handle_transaction(&normal(1, 1, deposit("500")))
+            .unwrap();
+        // scores below the threshold, but tx 1 is waiting for review
+        ledger
+            .handle_transaction(&normal(1, 1, deposit("10")))
+            .unwrap();
+        assert!(ledger.get_account(&1).is_none());
+        ledger
+            .handle_transaction(&review(1, 1, ReviewTransaction::Approve))
+            .unwrap();
+        assert_eq!(
+            ledger.get_account(&1).unwrap().total(&currency),
+            amount("500")
+        );
+        assert!(ledger.verify().unwrap().is_ok());
+    }
+
+    #[test]
+    fn new_client_opens_on_approval() {
+        let mut ledger = Ledger::default().with_fraud(FraudPolicy {
+            checks: vec![Box::new(Large)],
+            reject_score: None,
+            review_score: Some(50),
+        });
+        let currency = Currency::default();
+        let deposit = |value| NormalTransaction::Deposit {
+            amount: amount(value),
+            currency,
+        };
+        ledger
+            .handle_transaction(&normal(1, 1, deposit("500")))
+            .unwrap();
+        ledger
+            .handle_transaction(&normal(2, 2, deposit("500")))
+            .unwrap();
+        assert_eq!(ledger.pending().len(), 2);
+        assert!(ledger.get_account(&1).is_none());
+        assert!(ledger.verify().unwrap().is_ok());
+
+        ledger
+            .handle_transaction(&review(1, 1, ReviewTransaction::Decline))
+            .unwrap();
+        // opened by another deposit while the first one waits
+        ledger
+            .handle_transaction(&normal(2, 3, deposit("10")))
+            .unwrap();
+        ledger
+            .handle_transaction(&review(2, 2, ReviewTransaction::Approve))
+            .unwrap();
+        assert!(ledger.get_account(&1).is_none());
+        let rows: Vec<_> = ledger.rows().collect();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].total, amount("510"));
+        assert_eq!(rows[0].pending_deposits, Amount::ZERO);
+        assert!(ledger.verify().unwrap().is_ok());
+    }
+}
@@ -0,0 +1,359 @@
+use std::{collections::BTreeMap, fmt, time::Instant};
+
+use serde::Serialize;
+use thiserror::Error;
+use tracing::{debug, warn, Span};
+
+use super::{
+    account::AccountStatus,
+    ledger::{Errors, Ledger, Result},
+    limits::Rejection,
+    transaction::{
+        ControlTransaction, NormalTransaction, ReviewTransaction, SettlementTransaction,
+        Transaction, TryFromCsvTxError,
+    },
+};
+use crate::{Amount, CsvTransaction, Currency};
+
+/// Why an input record couldn't be turned into a transaction.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseFailure {
+    #[error("row couldn't be read")]
+    Malformed,
+    #[error("unknown type or unparsable field")]
+    Invalid,
+    #[error("missing or non-positive amount or missing receiver")]
+    Incomplete,
+}
+
+impl From<TryFromCsvTxError> for ParseFailure {
+    fn from(_: TryFromCsvTxError) -> Self {
+        ParseFailure::Incomplete
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    Deposit,
+    Withdrawal,
+    Transfer,
+    Dispute,
+    Resolve,
+    Chargeback,
+    ChargebackReversal,
+    Approve,
+    Decline,
+    Interest,
+}
+
+impl From<&Transaction> for RecordKind {
+    fn from(tx: &Transaction) -> Self {
+        match tx {
+            Transaction::Normal { kind, .. } => match kind {
+                NormalTransaction::Deposit { .. } => RecordKind::Deposit,
+                NormalTransaction::Withdraw { .. } => RecordKind::Withdrawal,
+                NormalTransaction::Transfer { .. } => RecordKind::Transfer,
+            },
+            Transaction::SettlementTransaction { kind, .. } => RecordKind::from(kind),
+            Transaction::Review { kind, .. } => match kind {
+                ReviewTransaction::Approve => RecordKind::Approve,
+                ReviewTransaction::Decline => RecordKind::Decline,
+            },
+            Transaction::Control {
+                kind: ControlTransaction::PostInterest,
+                ..
+            } => RecordKind::Interest,
+        }
+    }
+}
+
+impl From<&SettlementTransaction> for RecordKind {
+    fn from(tx: &SettlementTransaction) -> Self {
+        match tx {
+            SettlementTransaction::Dispute => RecordKind::Dispute,
+            SettlementTransaction::Resolve => RecordKind::Resolve,
+            SettlementTransaction::Chargeback => RecordKind::Chargeback,
+            SettlementTransaction::Reversal => RecordKind::ChargebackReversal,
+        }
+    }
+}
+
+/// Why the ledger left a transaction without effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ignored {
+    UnknownClient,
+    /// the referenced transaction isn't known (or not waiting for review)
+    UnknownTx,
+    DuplicateTx,
+    FrozenAccount,
+    InsufficientFunds,
+    SelfTransfer,
+    /// settlements of withdrawals
+    NotDisputable,
+    /// the dispute lifecycle has no transition for it
+    InvalidTransition,
+    /// more than what's left to dispute or settle
+    InvalidAmount,
+    DisputeWindowExpired,
+    Limit,
+    Fraud,
+    IndexUnavailable,
+}
+
+/// Why the last transaction had no effect (yet), read by [`Ledger::tally`].
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Skipped {
+    /// waiting for review or for the transaction it references
+    Deferred,
+    Ignored(Ignored),
+}
+
+/// Counters the ledger keeps while handling transactions.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Stats {
+    /// records passed to [`Ledger::handle_record`]
+    pub records_read: u64,
+    pub parse_failures: BTreeMap<ParseFailure, u64>,
+    pub applied: BTreeMap<RecordKind, u64>,
+    /// parked settlements and transactions queued for review, they're counted again
+    /// once they're applied or ignored
+    pub deferred: BTreeMap<RecordKind, u64>,
+    pub ignored: BTreeMap<Ignored, u64>,
+    pub deposited: BTreeMap<Currency, Amount>,
+    pub withdrawn: BTreeMap<Currency, Amount>,
+    /// records added to the transaction index, evicted ones included
+    pub indexed: u64,
+    #[serde(skip)]
+    pub(super) started: Option<Instant>,
+}
+
+/// [`Stats`] with the ledger state at the end of a run.
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    #[serde(flatten)]
+    pub stats: Stats,
+    pub clients: usize,
+    pub frozen: usize,
+    pub held: BTreeMap<Currency, Amount>,
+    /// net of reversals
+    pub charged_back: BTreeMap<Currency, Amount>,
+    /// since the first transaction
+    pub elapsed_secs: f64,
+    /// transactions handled per second
+    pub throughput: f64,
+}
+
+impl Ledger {
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Converts and applies a record of the input, parse failures are counted and returned.
+    pub fn handle_record(
+        &mut self,
+        record: std::result::Result<CsvTransaction, ParseFailure>,
+    ) -> Result {
+        self.stats.records_read += 1;
+        let tx = record.and_then(|x| Ok(Transaction::try_from(x)?));
+        match tx {
+            Ok(tx) => self.handle_transaction(&tx),
+            Err(failure) => {
+                debug!(%failure, "skipping input record");
+                *self.stats.parse_failures.entry(failure).or_default() += 1;
+                Err(Errors::Parse(failure))
+            }
+        }
+    }
+
+    pub(super) fn skip(&mut self, reason: Ignored) {
+        debug!(?reason, "transaction ignored");
+        self.skipped = Some(Skipped::Ignored(reason));
+    }
+
+    /// Counts the outcome of a transaction of `kind`, resets what its handler recorded.
+    pub(super) fn tally(&mut self, kind: RecordKind, result: &Result) {
+        let skipped = self.skipped.take();
+        let ignored = match result {
+            // chargebacks report the account they froze
+            Ok(()) | Err(Errors::AccountFrozen) => {
+                if skipped.is_none() {
+                    debug!("transaction applied");
+                }
+                return self.count(kind, skipped);
+            }
+            Err(Errors::Parse(_)) => return,
+            Err(Errors::AccountDoesNotExist) => Ignored::UnknownClient,
+            Err(Errors::DisputedNonExistant) => Ignored::UnknownTx,
+            Err(Errors::DisputeWindowExpired(_)) => Ignored::DisputeWindowExpired,
+            Err(Errors::Rejected(Rejection::Fraud { .. })) => Ignored::Fraud,
+            Err(Errors::Rejected(_)) => Ignored::Limit,
+            Err(Errors::Index(_)) => Ignored::IndexUnavailable,
+        };
+        if let Err(err) = result {
+            match ignored {
+                Ignored::IndexUnavailable => warn!(%err, "transaction failed"),
+                _ => debug!(%err, "transaction rejected"),
+            }
+        }
+        self.count(kind, Some(Skipped::Ignored(ignored)));
+    }
+
+    fn count(&mut self, kind: RecordKind, skipped: Option<Skipped>) {
+        let outcome = match skipped {
+            None => "applied",
+            Some(Skipped::Deferred) => "deferred",
+            Some(Skipped::Ignored(_)) => "ignored",
+        };
+        Span::current().record("outcome", outcome);
+        match skipped {
+            None => *self.stats.applied.entry(kind).or_default() += 1,
+            Some(Skipped::Deferred) => *self.stats.deferred.entry(kind).or_default() += 1,
+            Some(Skipped::Ignored(reason)) => *self.stats.ignored.entry(reason).or_default() += 1,
+        }
+        #[cfg(feature = "metrics")]
+        self.metrics.count(kind, outcome);
+    }
+
+    pub fn summary(&self) -> Summary {
+        let mut held = BTreeMap::<Currency, Amount>::new();
+        let mut charged_back = BTreeMap::<Currency, Amount>::new();
+        for client in self.accounts.values() {
+            for (currency, wallet) in &client.account.wallets {
+                *held.entry(*currency).or_default() += wallet.held.amount;
+            }
+            for (currency, amount) in &client.charged_back {
+                *charged_back.entry(*currency).or_default() += *amount;
+            }
+        }
+        let elapsed_secs = self
+            .stats
+            .started
+            .map_or(0.0, |x| x.elapsed().as_secs_f64());
+        let handled = self.sequence as f64;
+        Summary {
+            stats: self.stats.clone(),
+            clients: self.accounts.len(),
+            frozen: self
+                .accounts
+                .values()
+                .filter(|x| matches!(x.account.status, AccountStatus::Frozen))
+                .count(),
+            held,
+            charged_back,
+            elapsed_secs,
+            throughput: if elapsed_secs > 0.0 {
+                handled / elapsed_secs
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+fn write_counts<K: fmt::Debug>(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    counts: &BTreeMap<K, u64>,
+) -> fmt::Result {
+    write!(f, "{name}: {}", counts.values().sum::<u64>())?;
+    for (key, count) in counts {
+        write!(f, ", {key:?} {count}")?;
+    }
+    writeln!(f)
+}
+
+fn write_amounts(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    amounts: &BTreeMap<Currency, Amount>,
+) -> fmt::Result {
+    write!(f, "{name}:")?;
+    for (currency, amount) in amounts {
+        write!(f, " {amount} '{currency}'")?;
+    }
+    writeln!(f)
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = &self.stats;
+        writeln!(f, "records read: {}", stats.records_read)?;
+        write_counts(f, "parse failures", &stats.parse_failures)?;
+        write_counts(f, "applied", &stats.applied)?;
+        write_counts(f, "deferred", &stats.deferred)?;
+        write_counts(f, "ignored", &stats.ignored)?;
+        writeln!(f, "clients: {}, frozen: {}", self.clients, self.frozen)?;
+        write_amounts(f, "deposited", &stats.deposited)?;
+        write_amounts(f, "withdrawn", &stats.withdrawn)?;
+        write_amounts(f, "held", &self.held)?;
+        write_amounts(f, "charged back", &self.charged_back)?;
+        write!(
+            f,
+            "elapsed: {:.3}s, {:.0} transactions/s",
+            self.elapsed_secs, self.throughput
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use crate::{core::ledger::Ledger, read_records, Amount, Currency};
+
+    use super::{Ignored, ParseFailure, RecordKind};
+
+    #[test]
+    fn counts_a_run() {
+        let input = "type,client,tx,amount
+deposit,1,1,10
+deposit,1,1,10
+withdrawal,1,2,50
+withdrawal,1,3,4
+unknown,1,4,1
+deposit,1,5,
+dispute,1,1,
+dispute,1,9,
+chargeback,1,1,
+deposit,1,6,1
+withdrawal,2,7,1";
+        let mut ledger = Ledger::default();
+        for record in read_records(input.as_bytes()).unwrap() {
+            let _ = ledger.handle_record(record);
+        }
+        let summary = ledger.summary();
+        let stats = &summary.stats;
+        assert_eq!(stats.records_read, 11);
+        assert_eq!(
+            stats.parse_failures,
+            BTreeMap::from([(ParseFailure::Invalid, 1), (ParseFailure::Incomplete, 1)])
+        );
+        assert_eq!(
+            stats.applied,
+            BTreeMap::from([
+                (RecordKind::Deposit, 1),
+                (RecordKind::Withdrawal, 1),
+                (RecordKind::Dispute, 1),
+                (RecordKind::Chargeback, 1),
+            ])
+        );
+        assert_eq!(
+            stats.ignored,
+            BTreeMap::from([
+                (Ignored::UnknownClient, 1),
+                (Ignored::UnknownTx, 1),
+                (Ignored::DuplicateTx, 1),
+                (Ignored::FrozenAccount, 1),
+                (Ignored::InsufficientFunds, 1),
+            ])
+        );
+        let currency = Currency::default();
+        assert_eq!(stats.deposited[&currency], Amount::from_num(10));
+        assert_eq!(stats.withdrawn[&currency], Amount::from_num(4));
+        assert_eq!(summary.charged_back[&currency], Amount::from_num(10));
+        assert_eq!((summary.clients, summary.frozen), (1, 1));
+    }
+}
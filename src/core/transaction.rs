@@ -1,15 +1,42 @@
 use thiserror::Error;
 
-use crate::{Amount, ClientId, CsvTransaction, CsvTransactionKind, TxId};
+use crate::{Amount, ClientId, CsvTransaction, CsvTransactionKind, Currency, Timestamp, TxId};
 
+#[derive(Debug, Clone)]
 pub enum NormalTransaction {
-    Deposit { amount: Amount },
-    Withdraw { amount: Amount },
+    Deposit {
+        amount: Amount,
+        currency: Currency,
+    },
+    Withdraw {
+        amount: Amount,
+        currency: Currency,
+    },
+    /// moves funds from the transaction's client to `to`
+    Transfer {
+        amount: Amount,
+        currency: Currency,
+        to: ClientId,
+    },
 }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SettlementTransaction {
     Dispute,
     Resolve,
     Chargeback,
+    /// the merchant won representment, charged back funds go back to the client
+    Reversal,
+}
+
+/// Admin decisions on transactions waiting for review, see [`super::review`].
+pub enum ReviewTransaction {
+    Approve,
+    Decline,
+}
+
+/// Records that don't belong to a client, they drive periodic ledger work.
+pub enum ControlTransaction {
+    PostInterest,
 }
 
 pub enum Transaction {
@@ -17,18 +44,40 @@ pub enum Transaction {
         client_id: ClientId,
         tx_id: TxId,
         kind: NormalTransaction,
+        timestamp: Option<Timestamp>,
     },
     SettlementTransaction {
         client_id: ClientId,
         tx_id: TxId,
         kind: SettlementTransaction,
+        /// part of the transaction to dispute, resolve or charge back, `None` for all of it
+        amount: Option<Amount>,
+        timestamp: Option<Timestamp>,
+    },
+    Review {
+        client_id: ClientId,
+        tx_id: TxId,
+        kind: ReviewTransaction,
+        timestamp: Option<Timestamp>,
+    },
+    Control {
+        kind: ControlTransaction,
+        timestamp: Option<Timestamp>,
     },
 }
 
 #[derive(Error, Debug)]
-#[error("Invalid amount in transaction body")]
+#[error("Invalid amount or missing receiver in transaction body")]
 pub struct TryFromCsvTxError;
 
+/// settlements without an amount act on the whole transaction
+fn partial(amount: Option<Amount>) -> Result<Option<Amount>, TryFromCsvTxError> {
+    match amount {
+        Some(x) if x <= Amount::ZERO => Err(TryFromCsvTxError),
+        amount => Ok(amount),
+    }
+}
+
 impl TryFrom<CsvTransaction> for Transaction {
     type Error = TryFromCsvTxError;
 
@@ -37,39 +86,114 @@ impl TryFrom<CsvTransaction> for Transaction {
             CsvTransactionKind::Deposit => Self::Normal {
                 client_id: value.client_id,
                 tx_id: value.tx_id,
+                timestamp: value.timestamp,
                 kind: NormalTransaction::Deposit {
                     amount: value
                         .amount
                         .filter(|x| *x > Amount::ZERO)
                         .ok_or(TryFromCsvTxError)?,
+                    currency: value.currency,
                 },
             },
             CsvTransactionKind::Withdraw => Self::Normal {
                 client_id: value.client_id,
                 tx_id: value.tx_id,
+                timestamp: value.timestamp,
                 kind: NormalTransaction::Withdraw {
                     amount: value
                         .amount
                         .filter(|x| *x > Amount::ZERO)
                         .ok_or(TryFromCsvTxError)?,
+                    currency: value.currency,
+                },
+            },
+            CsvTransactionKind::Transfer => Self::Normal {
+                client_id: value.client_id,
+                tx_id: value.tx_id,
+                timestamp: value.timestamp,
+                kind: NormalTransaction::Transfer {
+                    amount: value
+                        .amount
+                        .filter(|x| *x > Amount::ZERO)
+                        .ok_or(TryFromCsvTxError)?,
+                    currency: value.currency,
+                    to: value.to_client.ok_or(TryFromCsvTxError)?,
                 },
             },
             CsvTransactionKind::Dispute => Self::SettlementTransaction {
                 client_id: value.client_id,
                 tx_id: value.tx_id,
+                timestamp: value.timestamp,
                 kind: SettlementTransaction::Dispute,
+                amount: partial(value.amount)?,
             },
             CsvTransactionKind::Resolve => Self::SettlementTransaction {
                 client_id: value.client_id,
                 tx_id: value.tx_id,
+                timestamp: value.timestamp,
                 kind: SettlementTransaction::Resolve,
+                amount: partial(value.amount)?,
             },
             CsvTransactionKind::Chargeback => Self::SettlementTransaction {
                 client_id: value.client_id,
                 tx_id: value.tx_id,
+                timestamp: value.timestamp,
                 kind: SettlementTransaction::Chargeback,
+                amount: partial(value.amount)?,
+            },
+            CsvTransactionKind::ChargebackReversal => Self::SettlementTransaction {
+                client_id: value.client_id,
+                tx_id: value.tx_id,
+                timestamp: value.timestamp,
+                kind: SettlementTransaction::Reversal,
+                amount: partial(value.amount)?,
+            },
+            CsvTransactionKind::Approve => Self::Review {
+                client_id: value.client_id,
+                tx_id: value.tx_id,
+                timestamp: value.timestamp,
+                kind: ReviewTransaction::Approve,
+            },
+            CsvTransactionKind::Decline => Self::Review {
+                client_id: value.client_id,
+                tx_id: value.tx_id,
+                timestamp: value.timestamp,
+                kind: ReviewTransaction::Decline,
+            },
+            CsvTransactionKind::Interest => Self::Control {
+                timestamp: value.timestamp,
+                kind: ControlTransaction::PostInterest,
             },
         };
         Ok(translated)
     }
 }
+
+impl Transaction {
+    /// `None` for control records
+    pub fn client_id(&self) -> Option<ClientId> {
+        match self {
+            Transaction::Normal { client_id, .. }
+            | Transaction::SettlementTransaction { client_id, .. }
+            | Transaction::Review { client_id, .. } => Some(*client_id),
+            Transaction::Control { .. } => None,
+        }
+    }
+    /// `None` for control records
+    pub fn tx_id(&self) -> Option<TxId> {
+        match self {
+            Transaction::Normal { tx_id, .. }
+            | Transaction::SettlementTransaction { tx_id, .. }
+            | Transaction::Review { tx_id, .. } => Some(*tx_id),
+            Transaction::Control { .. } => None,
+        }
+    }
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        match self {
+            Transaction::Normal { timestamp, .. }
+            | Transaction::SettlementTransaction { timestamp, .. }
+            | Transaction::Review { timestamp, .. }
+            | Transaction::Control { timestamp, .. } => *timestamp,
+        }
+    }
+}
@@ -0,0 +1,377 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, io,
+};
+
+use thiserror::Error;
+
+use super::{account::AccountStatus, index::TxRecord, ledger::Ledger};
+use crate::{Amount, ClientId, Currency, TxId};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Violation {
+    #[error(
+        "ledger is unbalanced in '{currency}': liabilities {liabilities} + house {house} + accounts total {accounts_total} != 0"
+    )]
+    Unbalanced {
+        currency: Currency,
+        liabilities: Amount,
+        /// house accounts of the ledger itself: posted interest and fee revenue
+        house: Amount,
+        accounts_total: Amount,
+    },
+    #[error(
+        "client {client}: held {held} in '{currency}' doesn't match disputed deposits {disputed}"
+    )]
+    HeldMismatch {
+        client: ClientId,
+        currency: Currency,
+        held: Amount,
+        disputed: Amount,
+    },
+    #[error("client {client}: held balance in '{currency}' is negative ({held})")]
+    NegativeHeld {
+        client: ClientId,
+        currency: Currency,
+        held: Amount,
+    },
+    #[error(
+        "client {client}: reserved {reserved} in '{currency}' doesn't match pending transactions {pending}"
+    )]
+    ReservedMismatch {
+        client: ClientId,
+        currency: Currency,
+        reserved: Amount,
+        pending: Amount,
+    },
+    #[error("client {client}: account is frozen without a chargeback")]
+    FrozenWithoutChargeback { client: ClientId },
+    #[error("client {client}: account has a chargeback but isn't frozen")]
+    ChargebackWithoutFreeze { client: ClientId },
+    #[error("client {client}: tx {tx_id} is indexed for a client without an account")]
+    UnknownClient { client: ClientId, tx_id: TxId },
+    #[error("client {client}: tx {tx_id} is indexed with a non-positive amount {amount}")]
+    NonPositiveAmount {
+        client: ClientId,
+        tx_id: TxId,
+        amount: Amount,
+    },
+}
+
+/// Result of [`Ledger::verify`], lists every broken invariant.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub violations: Vec<Violation>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return write!(f, "ledger is consistent");
+        }
+        writeln!(f, "ledger has {} violation(s):", self.violations.len())?;
+        for violation in &self.violations {
+            writeln!(f, " - {violation}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Ledger {
+    /// Walks the whole ledger and checks its invariants:
+    /// - double-entry per currency: `liabilites + house + sum(account totals) == 0`
+    /// - per client and currency `held` equals the sum of currently disputed deposits and is
+    ///   never negative
+    /// - per client and currency `reserved` equals the funds set aside for pending transactions
+    /// - an account is frozen if and only if one of its deposits was charged back, a reversed
+    ///   chargeback may leave it frozen
+    /// - the transaction index only holds positive amounts of known clients
+    ///
+    /// A ledger rebuilt by [`Ledger::from_events`] has no transactions to match `held` and
+    /// `reserved` against, they're taken as they are.
+    pub fn verify(&self) -> io::Result<Report> {
+        let mut violations = Vec::new();
+        // per client and currency: sum of disputed deposits
+        let mut disputed: BTreeMap<(ClientId, Currency), Amount> = BTreeMap::new();
+        // clients with a charged back deposit in the index
+        let mut charged_back: BTreeSet<ClientId> = BTreeSet::new();
+        self.index.for_each(&mut |client, tx_id, record| {
+            // disputed transfers are held (and charged back) at the receiver
+            let held_by = match record {
+                TxRecord::Deposit(deposit) => Some((client, deposit)),
+                TxRecord::Transfer { to, outcome } => Some((*to, outcome)),
+                TxRecord::Withdrawal { .. } => None,
+            };
+            if let Some((holder, deposit)) = held_by {
+                if deposit.disputed() > Amount::ZERO {
+                    *disputed.entry((holder, deposit.currency())).or_default() +=
+                        deposit.disputed();
+                }
+                if deposit.charged_back() > Amount::ZERO {
+                    charged_back.insert(holder);
+                }
+                if !self.accounts.contains_key(&holder) {
+                    violations.push(Violation::UnknownClient {
+                        client: holder,
+                        tx_id,
+                    });
+                }
+            }
+            let amount = record.amount();
+            if amount <= Amount::ZERO {
+                violations.push(Violation::NonPositiveAmount {
+                    client,
+                    tx_id,
+                    amount,
+                });
+            }
+            if held_by.is_none_or(|(holder, _)| holder != client)
+                && !self.accounts.contains_key(&client)
+            {
+                violations.push(Violation::UnknownClient { client, tx_id });
+            }
+        })?;
+
+        let mut pending: BTreeMap<(ClientId, Currency), Amount> = BTreeMap::new();
+        // deposits reserve nothing, a new client's one isn't on any account yet
+        for (&(client, _), tx) in self
+            .pending
+            .iter()
+            .filter(|(_, x)| x.reserved > Amount::ZERO)
+        {
+            *pending.entry((client, tx.currency())).or_default() += tx.reserved;
+        }
+
+        let mut accounts_total: BTreeMap<Currency, Amount> = BTreeMap::new();
+        for (&client, entry) in &self.accounts {
+            let account = &entry.account;
+            for (&currency, wallet) in &account.wallets {
+                *accounts_total.entry(currency).or_default() += wallet.total();
+                let held = wallet.held.amount;
+                let disputed = disputed.remove(&(client, currency)).unwrap_or_default();
+                if held != disputed && !self.projected {
+                    violations.push(Violation::HeldMismatch {
+                        client,
+                        currency,
+                        held,
+                        disputed,
+                    });
+                }
+                let reserved = wallet.reserved.amount;
+                let pending = pending.remove(&(client, currency)).unwrap_or_default();
+                if reserved != pending && !self.projected {
+                    violations.push(Violation::ReservedMismatch {
+                        client,
+                        currency,
+                        reserved,
+                        pending,
+                    });
+                }
+                if held < Amount::ZERO {
+                    violations.push(Violation::NegativeHeld {
+                        client,
+                        currency,
+                        held,
+                    });
+                }
+            }
+            // charged back deposits may have been evicted, the client keeps count of them
+            let has_chargeback = charged_back.contains(&client) || entry.chargebacks > 0;
+            match (account.status, has_chargeback) {
+                // reversed chargebacks may leave the account frozen
+                (AccountStatus::Frozen, false) if entry.reversals > 0 => {}
+                (AccountStatus::Frozen, false) => {
+                    violations.push(Violation::FrozenWithoutChargeback { client })
+                }
+                (AccountStatus::Active, true) => {
+                    violations.push(Violation::ChargebackWithoutFreeze { client })
+                }
+                _ => {}
+            }
+        }
+        // disputes in a currency the client has no wallet for
+        for ((client, currency), disputed) in disputed {
+            violations.push(Violation::HeldMismatch {
+                client,
+                currency,
+                held: Amount::ZERO,
+                disputed,
+            });
+        }
+
+        // reservations in a currency the client has no wallet for
+        for ((client, currency), pending) in pending {
+            violations.push(Violation::ReservedMismatch {
+                client,
+                currency,
+                reserved: Amount::ZERO,
+                pending,
+            });
+        }
+
+        let currencies: BTreeSet<Currency> = self
+            .liabilites
+            .keys()
+            .chain(self.interest_house.keys())
+            .chain(self.revenue.keys())
+            .chain(accounts_total.keys())
+            .copied()
+            .collect();
+        for currency in currencies {
+            let liabilities = self.liabilities(&currency);
+            let house = self.interest_house(&currency) + self.revenue(&currency);
+            let accounts_total = accounts_total.get(&currency).copied().unwrap_or_default();
+            if liabilities + house + accounts_total != Amount::ZERO {
+                violations.push(Violation::Unbalanced {
+                    currency,
+                    liabilities,
+                    house,
+                    accounts_total,
+                });
+            }
+        }
+        Ok(Report { violations })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            account::AccountStatus,
+            index::TxRecord,
+            ledger::Ledger,
+            transaction::{NormalTransaction, SettlementTransaction, Transaction},
+        },
+        Amount, Currency,
+    };
+
+    use super::Violation;
+
+    fn populated_ledger() -> Ledger {
+        let mut ledger = Ledger::default();
+        let txs = [
+            Transaction::Normal {
+                client_id: 1,
+                tx_id: 1,
+                kind: NormalTransaction::Deposit {
+                    amount: Amount::from_str("10").unwrap(),
+                    currency: Currency::default(),
+                },
+                timestamp: None,
+            },
+            Transaction::Normal {
+                client_id: 1,
+                tx_id: 2,
+                kind: NormalTransaction::Deposit {
+                    amount: Amount::from_str("2.5").unwrap(),
+                    currency: Currency::default(),
+                },
+                timestamp: None,
+            },
+            Transaction::SettlementTransaction {
+                client_id: 1,
+                tx_id: 2,
+                kind: SettlementTransaction::Dispute,
+                amount: None,
+                timestamp: None,
+            },
+            Transaction::Normal {
+                client_id: 2,
+                tx_id: 3,
+                kind: NormalTransaction::Deposit {
+                    amount: Amount::from_str("1").unwrap(),
+                    currency: Currency::default(),
+                },
+                timestamp: None,
+            },
+            Transaction::SettlementTransaction {
+                client_id: 2,
+                tx_id: 3,
+                kind: SettlementTransaction::Dispute,
+                amount: None,
+                timestamp: None,
+            },
+            Transaction::SettlementTransaction {
+                client_id: 2,
+                tx_id: 3,
+                kind: SettlementTransaction::Chargeback,
+                amount: None,
+                timestamp: None,
+            },
+        ];
+        for tx in &txs {
+            let _ = ledger.handle_transaction(tx);
+        }
+        ledger
+    }
+
+    #[test]
+    fn consistent_ledger() {
+        let ledger = populated_ledger();
+        let report = ledger.verify().unwrap();
+        assert!(report.is_ok(), "{report}");
+    }
+
+    #[test]
+    fn detects_violations() {
+        let mut ledger = populated_ledger();
+        let currency = Currency::default();
+        ledger.liabilites.get_mut(&currency).unwrap().amount += Amount::from_str("1").unwrap();
+        let client = ledger.accounts.get_mut(&1).unwrap();
+        client
+            .account
+            .wallets
+            .get_mut(&currency)
+            .unwrap()
+            .held
+            .amount = Amount::ZERO;
+        client.account.status = AccountStatus::Frozen;
+        ledger.accounts.get_mut(&2).unwrap().account.status = AccountStatus::Active;
+        let withdrawal = TxRecord::Withdrawal {
+            amount: Amount::ZERO,
+            currency,
+            seq: 7,
+            timestamp: None,
+            fee: Amount::ZERO,
+            score: 0,
+        };
+        ledger.index.insert(3, 4, withdrawal).unwrap();
+
+        let report = ledger.verify().unwrap();
+        assert_eq!(
+            report.violations,
+            vec![
+                Violation::NonPositiveAmount {
+                    client: 3,
+                    tx_id: 4,
+                    amount: Amount::ZERO
+                },
+                Violation::UnknownClient {
+                    client: 3,
+                    tx_id: 4
+                },
+                Violation::HeldMismatch {
+                    client: 1,
+                    currency,
+                    held: Amount::ZERO,
+                    disputed: Amount::from_str("2.5").unwrap()
+                },
+                Violation::FrozenWithoutChargeback { client: 1 },
+                Violation::ChargebackWithoutFreeze { client: 2 },
+                Violation::Unbalanced {
+                    currency,
+                    liabilities: Amount::from_str("-11.5").unwrap(),
+                    house: Amount::ZERO,
+                    accounts_total: Amount::from_str("10").unwrap(),
+                },
+            ]
+        );
+    }
+}
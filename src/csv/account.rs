@@ -1,6 +1,6 @@
-use serde::{Deserialize, Serialize};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 
-use crate::{Amount, ClientId};
+use crate::{Amount, ClientId, Currency};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct Account {
@@ -12,25 +12,103 @@ pub struct Account {
     #[serde(default, with = "super::amount::my_amount")]
     pub total: Amount,
     pub locked: bool,
+    /// empty for the default currency
+    #[serde(default)]
+    pub currency: Currency,
+    /// negative part of `available`, drawn from a credit line or left by a chargeback
+    #[serde(default, with = "super::amount::my_amount")]
+    pub credit_used: Amount,
+    /// withdrawals and outgoing transfers waiting for review, included in `total`
+    #[serde(default, with = "super::amount::my_amount")]
+    pub pending_withdrawals: Amount,
+    /// deposits waiting for review, not included in `total`
+    #[serde(default, with = "super::amount::my_amount")]
+    pub pending_deposits: Amount,
+}
+
+/// Optional columns of the account report, the first five are always written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Columns {
+    pub currency: bool,
+    /// `credit_used`
+    pub credit: bool,
+    /// `pending_withdrawals` and `pending_deposits`
+    pub pending: bool,
+}
+
+impl Columns {
+    pub fn all() -> Self {
+        Self {
+            currency: true,
+            credit: true,
+            pending: true,
+        }
+    }
+}
+
+/// An [`Account`] written with the selected [`Columns`] only.
+pub(super) struct AccountRow<'a>(pub &'a Account, pub Columns);
+
+impl Serialize for AccountRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let AccountRow(account, columns) = self;
+        let mut row = serializer.serialize_struct("Account", 9)?;
+        row.serialize_field("client", &account.client)?;
+        row.serialize_field("available", &account.available.to_string())?;
+        row.serialize_field("held", &account.held.to_string())?;
+        row.serialize_field("total", &account.total.to_string())?;
+        row.serialize_field("locked", &account.locked)?;
+        if columns.currency {
+            row.serialize_field("currency", &account.currency)?;
+        }
+        if columns.credit {
+            row.serialize_field("credit_used", &account.credit_used.to_string())?;
+        }
+        if columns.pending {
+            row.serialize_field(
+                "pending_withdrawals",
+                &account.pending_withdrawals.to_string(),
+            )?;
+            row.serialize_field("pending_deposits", &account.pending_deposits.to_string())?;
+        }
+        row.end()
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::Amount;
+    use crate::{Amount, Currency};
     use csv::ByteRecord;
     use expect_test::expect;
 
-    use super::Account;
+    use super::{Account, AccountRow, Columns};
 
     fn example_data() -> Vec<Account> {
         let account = Account {
             client: 5,
             available: Amount::from_str("100.5500").unwrap(),
             held: Amount::from_str("50.2300").unwrap(),
-            total: Amount::from_str("50.2300").unwrap() + Amount::from_str("100.5500").unwrap(),
+            total: Amount::from_str("50.2300").unwrap()
+                + Amount::from_str("100.5500").unwrap()
+                + Amount::from_str("10").unwrap(),
             locked: false,
+            currency: Currency::default(),
+            credit_used: Amount::ZERO,
+            pending_withdrawals: Amount::from_str("10").unwrap(),
+            pending_deposits: Amount::ZERO,
         };
-        vec![account]
+        let euro = Account {
+            client: 5,
+            available: Amount::from_str("-1").unwrap(),
+            held: Amount::ZERO,
+            total: Amount::from_str("-1").unwrap(),
+            locked: false,
+            currency: "EUR".parse().unwrap(),
+            credit_used: Amount::from_str("1").unwrap(),
+            pending_withdrawals: Amount::ZERO,
+            pending_deposits: Amount::from_str("2.5").unwrap(),
+        };
+        vec![account, euro]
     }
     #[test]
     fn e2e_deser() {
@@ -65,8 +143,9 @@ mod test {
         let example_data = example_data();
 
         let expected = expect![[r#"
-            client,available,held,total,locked
-            5,100.55,50.23,150.78,false
+            client,available,held,total,locked,currency,credit_used,pending_withdrawals,pending_deposits
+            5,100.55,50.23,160.78,false,,0,10,0
+            5,-1,0,-1,false,EUR,1,0,2.5
         "#]];
 
         let mut buf = Vec::new();
@@ -81,4 +160,37 @@ mod test {
         let buf = String::from_utf8(buf).unwrap();
         expected.assert_eq(&buf);
     }
+
+    #[test]
+    fn selected_columns() {
+        let write = |columns| {
+            let mut wtr = csv::Writer::from_writer(Vec::new());
+            for i in &example_data() {
+                wtr.serialize(AccountRow(i, columns)).unwrap();
+            }
+            String::from_utf8(wtr.into_inner().unwrap()).unwrap()
+        };
+        expect![[r#"
+            client,available,held,total,locked
+            5,100.55,50.23,160.78,false
+            5,-1,0,-1,false
+        "#]]
+        .assert_eq(&write(Columns::default()));
+        expect![[r#"
+            client,available,held,total,locked,currency,pending_withdrawals,pending_deposits
+            5,100.55,50.23,160.78,false,,10,0
+            5,-1,0,-1,false,EUR,0,2.5
+        "#]]
+        .assert_eq(&write(Columns {
+            currency: true,
+            pending: true,
+            ..Default::default()
+        }));
+        let mut wtr = csv::Writer::from_writer(Vec::new());
+        for i in &example_data() {
+            wtr.serialize(i).unwrap();
+        }
+        let full = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        assert_eq!(write(Columns::all()), full);
+    }
 }
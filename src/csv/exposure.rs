@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::exposure::{AccountExposure, LiabilityExposure},
+    Amount, ClientId, Currency,
+};
+
+/// One row of the exposure report, a client balance that went below zero.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ExposureRecord {
+    pub client: ClientId,
+    /// empty for the default currency
+    #[serde(default)]
+    pub currency: Currency,
+    #[serde(with = "super::amount::my_amount")]
+    pub available: Amount,
+    #[serde(with = "super::amount::my_amount")]
+    pub total: Amount,
+    pub locked: bool,
+    pub chargebacks: usize,
+    #[serde(with = "super::amount::my_amount")]
+    pub charged_back: Amount,
+}
+
+impl From<AccountExposure> for ExposureRecord {
+    fn from(x: AccountExposure) -> Self {
+        ExposureRecord {
+            client: x.client,
+            currency: x.currency,
+            available: x.available,
+            total: x.total,
+            locked: x.frozen,
+            chargebacks: x.chargebacks,
+            charged_back: x.charged_back,
+        }
+    }
+}
+
+/// One row of the liabilities report, `deposits + chargebacks == liabilities`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct LiabilityRecord {
+    /// empty for the default currency
+    #[serde(default)]
+    pub currency: Currency,
+    #[serde(with = "super::amount::my_amount")]
+    pub liabilities: Amount,
+    #[serde(with = "super::amount::my_amount")]
+    pub deposits: Amount,
+    #[serde(with = "super::amount::my_amount")]
+    pub chargebacks: Amount,
+}
+
+impl From<LiabilityExposure> for LiabilityRecord {
+    fn from(x: LiabilityExposure) -> Self {
+        LiabilityRecord {
+            currency: x.currency,
+            liabilities: x.liabilities,
+            deposits: x.deposits,
+            chargebacks: x.chargebacks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expect_test::expect;
+
+    use crate::{core::exposure::AccountExposure, Amount};
+
+    use super::ExposureRecord;
+
+    #[test]
+    fn output_sample() {
+        let record = ExposureRecord::from(AccountExposure {
+            client: 4,
+            currency: "EUR".parse().unwrap(),
+            available: Amount::from_str("-70").unwrap(),
+            total: Amount::from_str("-20.5").unwrap(),
+            frozen: true,
+            chargebacks: 2,
+            charged_back: Amount::from_str("150").unwrap(),
+        });
+        let expected = expect![[r#"
+            client,currency,available,total,locked,chargebacks,charged_back
+            4,EUR,-70,-20.5,true,2,150
+        "#]];
+        let mut buf = Vec::new();
+        {
+            let mut wtr = csv::Writer::from_writer(&mut buf);
+            wtr.serialize(&record).unwrap();
+            wtr.flush().unwrap();
+        }
+        let buf = String::from_utf8(buf).unwrap();
+        expected.assert_eq(&buf);
+    }
+}
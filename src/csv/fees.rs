@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{core::fees::FeeTotals, Amount, ClientId, Currency};
+
+/// One row of the fees report, fees charged to a client in a single currency.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct FeeRecord {
+    pub client: ClientId,
+    /// empty for the default currency
+    #[serde(default)]
+    pub currency: Currency,
+    #[serde(with = "super::amount::my_amount")]
+    pub deposits: Amount,
+    #[serde(with = "super::amount::my_amount")]
+    pub withdrawals: Amount,
+    #[serde(with = "super::amount::my_amount")]
+    pub chargebacks: Amount,
+    #[serde(with = "super::amount::my_amount")]
+    pub total: Amount,
+}
+
+impl From<(&(ClientId, Currency), &FeeTotals)> for FeeRecord {
+    fn from((&(client, currency), totals): (&(ClientId, Currency), &FeeTotals)) -> Self {
+        FeeRecord {
+            client,
+            currency,
+            deposits: totals.deposits,
+            withdrawals: totals.withdrawals,
+            chargebacks: totals.chargebacks,
+            total: totals.total(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expect_test::expect;
+
+    use crate::{core::fees::FeeTotals, Amount};
+
+    use super::FeeRecord;
+
+    #[test]
+    fn output_sample() {
+        let totals = FeeTotals {
+            deposits: Amount::from_str("1.5").unwrap(),
+            withdrawals: Amount::from_str("0.25").unwrap(),
+            chargebacks: Amount::ZERO,
+        };
+        let record = FeeRecord::from((&(3, "EUR".parse().unwrap()), &totals));
+        let expected = expect![[r#"
+            client,currency,deposits,withdrawals,chargebacks,total
+            3,EUR,1.5,0.25,0,1.75
+        "#]];
+        let mut buf = Vec::new();
+        {
+            let mut wtr = csv::Writer::from_writer(&mut buf);
+            wtr.serialize(&record).unwrap();
+            wtr.flush().unwrap();
+        }
+        let buf = String::from_utf8(buf).unwrap();
+        expected.assert_eq(&buf);
+    }
+}
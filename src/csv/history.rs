@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{core::ledger::TransactionOutcomeKind, Amount, ClientId, Currency, Timestamp, TxId};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryKind {
+    Deposit,
+    Withdrawal,
+    Transfer,
+}
+
+/// One row of the transaction history report.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct HistoryRecord {
+    pub client: ClientId,
+    pub tx: TxId,
+    #[serde(rename = "type")]
+    pub kind: HistoryKind,
+    #[serde(with = "super::amount::my_amount")]
+    pub amount: Amount,
+    /// empty for the default currency
+    #[serde(default)]
+    pub currency: Currency,
+    #[serde(default, with = "super::amount::my_amount")]
+    pub fee: Amount,
+    /// dispute state of deposits and transfers, empty for withdrawals
+    pub state: Option<TransactionOutcomeKind>,
+    #[serde(default, with = "super::timestamp::my_timestamp_opt")]
+    pub timestamp: Option<Timestamp>,
+    /// receiving client of transfers
+    #[serde(default)]
+    pub to_client: Option<ClientId>,
+    /// fraud score of the transaction
+    #[serde(default)]
+    pub score: u32,
+}
+
+#[cfg(test)]
+mod test {
+    use expect_test::expect;
+
+    use crate::{core::ledger::TransactionOutcomeKind, Amount, Currency};
+
+    use super::{HistoryKind, HistoryRecord};
+
+    #[test]
+    fn output_sample() {
+        let example_data = [
+            HistoryRecord {
+                client: 1,
+                tx: 1,
+                kind: HistoryKind::Deposit,
+                amount: Amount::from_str("10.5").unwrap(),
+                currency: "EUR".parse().unwrap(),
+                state: Some(TransactionOutcomeKind::Chargeback),
+                timestamp: Some("2024-03-01T12:30:00Z".parse().unwrap()),
+                to_client: None,
+                fee: Amount::ZERO,
+                score: 0,
+            },
+            HistoryRecord {
+                client: 1,
+                tx: 2,
+                kind: HistoryKind::Withdrawal,
+                amount: Amount::from_str("1").unwrap(),
+                currency: Currency::default(),
+                state: None,
+                timestamp: None,
+                to_client: None,
+                fee: Amount::ZERO,
+                score: 0,
+            },
+            HistoryRecord {
+                client: 1,
+                tx: 3,
+                kind: HistoryKind::Transfer,
+                amount: Amount::from_str("2").unwrap(),
+                currency: Currency::default(),
+                state: Some(TransactionOutcomeKind::Disputed),
+                timestamp: None,
+                to_client: Some(2),
+                fee: Amount::ZERO,
+                score: 40,
+            },
+        ];
+        let expected = expect![[r#"
+            client,tx,type,amount,currency,fee,state,timestamp,to_client,score
+            1,1,deposit,10.5,EUR,0,chargeback,2024-03-01T12:30:00Z,,0
+            1,2,withdrawal,1,,0,,,,0
+            1,3,transfer,2,,0,disputed,,2,40
+        "#]];
+        let mut buf = Vec::new();
+        {
+            let mut wtr = csv::Writer::from_writer(&mut buf);
+            for i in &example_data {
+                wtr.serialize(i).unwrap();
+            }
+            wtr.flush().unwrap();
+        }
+        let buf = String::from_utf8(buf).unwrap();
+        expected.assert_eq(&buf);
+    }
+}
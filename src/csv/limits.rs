@@ -0,0 +1,76 @@
+use std::{collections::BTreeMap, io::Read};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{core::limits::Limits, Amount, ClientId};
+
+/// One row of the per-client limits file, empty columns fall back to the defaults.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct LimitsRecord {
+    pub client: ClientId,
+    #[serde(default, with = "super::amount::my_amount_opt")]
+    pub max_withdrawal: Option<Amount>,
+    #[serde(default, with = "super::amount::my_amount_opt")]
+    pub max_daily_withdrawal: Option<Amount>,
+    #[serde(default)]
+    pub max_deposits: Option<u32>,
+    #[serde(default, with = "super::amount::my_amount_opt")]
+    pub min_balance: Option<Amount>,
+    #[serde(default, with = "super::amount::my_amount_opt")]
+    pub credit_limit: Option<Amount>,
+}
+
+impl From<LimitsRecord> for (ClientId, Limits) {
+    fn from(record: LimitsRecord) -> Self {
+        let limits = Limits {
+            max_withdrawal: record.max_withdrawal,
+            max_daily_withdrawal: record.max_daily_withdrawal,
+            max_deposits: record.max_deposits,
+            min_balance: record.min_balance,
+            credit_limit: record.credit_limit,
+        };
+        (record.client, limits)
+    }
+}
+
+/// Reads per-client limits, unlike transactions a malformed row fails the whole file.
+pub fn read_limits<R: Read>(input: R) -> Result<BTreeMap<ClientId, Limits>, csv::Error> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .has_headers(true)
+        .flexible(true)
+        .from_reader(input)
+        .deserialize::<LimitsRecord>()
+        .map(|record| record.map(Into::into))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{core::limits::Limits, Amount};
+
+    use super::read_limits;
+
+    #[test]
+    fn overrides() {
+        let input =
+            "client, max_withdrawal, max_daily_withdrawal, max_deposits, min_balance, credit_limit
+1, 100, , 5, ,
+2, , , , -50.5, 200";
+        let limits = read_limits(input.as_bytes()).unwrap();
+        assert_eq!(
+            limits[&1],
+            Limits {
+                max_withdrawal: Some(Amount::from_num(100)),
+                max_deposits: Some(5),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            limits[&2].min_balance,
+            Some(Amount::from_str("-50.5").unwrap())
+        );
+        assert_eq!(limits[&2].credit_limit, Some(Amount::from_num(200)));
+        assert!(read_limits("client, max_deposits\n1, many".as_bytes()).is_err());
+    }
+}
@@ -1,49 +1,90 @@
-use std::{fs::File, io::Write, path::Path};
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+};
 
 use csv::{ByteRecord, Reader};
+use tracing::debug;
 
-use crate::CsvTransaction;
+use crate::{
+    core::{ledger::Ledger, stats::ParseFailure},
+    CsvTransaction,
+};
 
 pub mod account;
 mod amount;
+pub mod exposure;
+pub mod fees;
+pub mod history;
+pub mod limits;
+mod timestamp;
 pub mod transaction;
+pub mod unmatched;
+pub mod workload;
 
 pub fn read_from_file<T: AsRef<Path>>(
     path: T,
 ) -> Result<impl Iterator<Item = CsvTransaction>, csv::Error> {
+    read_from_reader(File::open(path)?)
+}
+
+pub fn read_from_reader<R: Read>(
+    input: R,
+) -> Result<impl Iterator<Item = CsvTransaction>, csv::Error> {
+    Ok(read_records(input)?.filter_map(Result::ok))
+}
+
+pub fn read_records_from_file<T: AsRef<Path>>(
+    path: T,
+) -> Result<impl Iterator<Item = Result<CsvTransaction, ParseFailure>>, csv::Error> {
+    read_records(File::open(path)?)
+}
+
+/// Like [`read_from_reader`] but keeps the rows that couldn't be parsed as errors,
+/// see [`Ledger::handle_record`].
+pub fn read_records<R: Read>(
+    input: R,
+) -> Result<impl Iterator<Item = Result<CsvTransaction, ParseFailure>>, csv::Error> {
     let mut reader = csv::ReaderBuilder::new()
         .trim(csv::Trim::All)
         .has_headers(true)
         .delimiter(b',')
         .flexible(true)
         .double_quote(false)
-        .from_path(path)?;
+        .from_reader(input);
     let mut byterec = ByteRecord::new();
     let headers = reader.byte_headers()?.clone();
     let mut is_finished = false;
     let value_iterator = std::iter::from_fn(move || {
-        fn call(
+        fn call<R: Read>(
             is_finished: &mut bool,
-            reader: &mut Reader<File>,
+            reader: &mut Reader<R>,
             headers: &ByteRecord,
             byterec: &mut ByteRecord,
-        ) -> Option<CsvTransaction> {
-            loop {
-                match reader.read_byte_record(byterec) {
-                    Ok(true) => {
-                        if let Ok(data) = byterec.deserialize::<CsvTransaction>(Some(headers)) {
-                            return Some(data);
-                        }
+        ) -> Option<Result<CsvTransaction, ParseFailure>> {
+            match reader.read_byte_record(byterec) {
+                Ok(true) => Some(
+                    byterec
+                        .deserialize::<CsvTransaction>(Some(headers))
+                        .map_err(|err| {
+                            debug!(%err, "invalid input row");
+                            ParseFailure::Invalid
+                        }),
+                ),
+                Ok(false) => {
+                    if let Ok(data) = byterec.deserialize::<CsvTransaction>(Some(headers)) {
+                        Some(Ok(data))
+                    } else {
+                        *is_finished = true;
+                        None
                     }
-                    Ok(false) => {
-                        if let Ok(data) = byterec.deserialize::<CsvTransaction>(Some(headers)) {
-                            return Some(data);
-                        } else {
-                            *is_finished = true;
-                            return None;
-                        }
-                    }
-                    Err(_) => {}
+                }
+                Err(err) => {
+                    debug!(%err, "malformed input row");
+                    // the input itself failed, nothing more can be read
+                    *is_finished = err.is_io_error();
+                    Some(Err(ParseFailure::Malformed))
                 }
             }
         }
@@ -55,7 +96,12 @@ pub fn read_from_file<T: AsRef<Path>>(
     Ok(value_iterator)
 }
 
-pub fn dump_to_csv<D, O>(data: impl Iterator<Item = D>, out: O) -> Result<(), csv::Error>
+/// Writes the account report, `columns` picks the optional columns.
+pub fn dump_to_csv<D, O>(
+    data: impl Iterator<Item = D>,
+    columns: account::Columns,
+    out: O,
+) -> Result<(), csv::Error>
 where
     D: Into<account::Account>,
     O: Write,
@@ -69,7 +115,85 @@ where
 
     for record in data {
         let for_writing: account::Account = record.into();
-        writer.serialize(&for_writing)?
+        writer.serialize(account::AccountRow(&for_writing, columns))?
+    }
+    Ok(())
+}
+
+/// Writes the fees charged to every client as [`fees::FeeRecord`] rows.
+pub fn dump_fees_to_csv<O: Write>(ledger: &Ledger, out: O) -> Result<(), csv::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b',')
+        .has_headers(true)
+        .flexible(false)
+        .double_quote(false)
+        .from_writer(out);
+
+    for record in ledger.fees() {
+        writer.serialize(fees::FeeRecord::from(record))?;
+    }
+    Ok(())
+}
+
+/// Writes every client balance below zero as [`exposure::ExposureRecord`] rows.
+pub fn dump_exposure_to_csv<O: Write>(ledger: &Ledger, out: O) -> Result<(), csv::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b',')
+        .has_headers(true)
+        .flexible(false)
+        .double_quote(false)
+        .from_writer(out);
+
+    for record in ledger.account_exposure() {
+        writer.serialize(exposure::ExposureRecord::from(record))?;
+    }
+    Ok(())
+}
+
+/// Writes the liabilities of every currency by cause as [`exposure::LiabilityRecord`] rows.
+pub fn dump_liabilities_to_csv<O: Write>(ledger: &Ledger, out: O) -> Result<(), csv::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b',')
+        .has_headers(true)
+        .flexible(false)
+        .double_quote(false)
+        .from_writer(out);
+
+    for record in ledger.liability_exposure() {
+        writer.serialize(exposure::LiabilityRecord::from(record))?;
+    }
+    Ok(())
+}
+
+/// Writes every retained deposit/withdrawal of the ledger as [`history::HistoryRecord`] rows.
+pub fn dump_history_to_csv<O: Write>(ledger: &Ledger, out: O) -> Result<(), csv::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b',')
+        .has_headers(true)
+        .flexible(false)
+        .double_quote(false)
+        .from_writer(out);
+
+    let mut result = Ok(());
+    ledger.visit_history(&mut |client, tx, record| {
+        if result.is_ok() {
+            result = writer.serialize(history::HistoryRecord::from((client, tx, record)));
+        }
+    })?;
+    result
+}
+
+/// Writes the settlements that never matched a transaction as [`unmatched::UnmatchedRecord`] rows.
+pub fn dump_unmatched_to_csv<O: Write>(ledger: &Ledger, out: O) -> Result<(), csv::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b',')
+        .has_headers(true)
+        .flexible(false)
+        .double_quote(false)
+        .from_writer(out);
+
+    for record in ledger.unmatched() {
+        writer.serialize(unmatched::UnmatchedRecord::from(record))?;
     }
     Ok(())
 }
@@ -0,0 +1,31 @@
+pub mod my_timestamp_opt {
+    use chrono::{DateTime, SecondsFormat};
+    use serde::{self, de, Deserialize, Deserializer, Serializer};
+
+    use crate::Timestamp;
+
+    pub fn serialize<S>(timestamp: &Option<Timestamp>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer
+            .serialize_some(&timestamp.map(|x| x.to_rfc3339_opts(SecondsFormat::AutoSi, true)))
+    }
+
+    /// accepts RFC 3339 or unix epoch seconds, empty column is `None`
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Timestamp>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: Option<&str> = Option::deserialize(deserializer)?;
+        s.filter(|s| !s.is_empty())
+            .map(|s| match s.parse::<i64>() {
+                Ok(secs) => DateTime::from_timestamp(secs, 0)
+                    .ok_or_else(|| de::Error::custom("epoch timestamp out of range")),
+                Err(_) => DateTime::parse_from_rfc3339(s)
+                    .map(|x| x.to_utc())
+                    .map_err(|e| de::Error::custom(e.to_string())),
+            })
+            .transpose()
+    }
+}
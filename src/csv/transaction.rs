@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::common::amount::Amount;
+use crate::{common::amount::Amount, ClientId, Currency, Timestamp};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -8,9 +8,17 @@ pub enum TransactionKind {
     Deposit,
     #[serde(rename = "withdrawal")]
     Withdraw,
+    Transfer,
     Dispute,
     Resolve,
     Chargeback,
+    #[serde(rename = "chargeback_reversal")]
+    ChargebackReversal,
+    /// admin decision on a transaction waiting for review
+    Approve,
+    Decline,
+    /// control record posting accrued interest, client and tx are ignored
+    Interest,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -23,6 +31,16 @@ pub struct Transaction {
     pub tx_id: u32,
     #[serde(default, with = "super::amount::my_amount_opt")]
     pub amount: Option<Amount>,
+    /// optional for backwards compatibility, RFC 3339 or unix epoch seconds
+    #[serde(default, with = "super::timestamp::my_timestamp_opt")]
+    pub timestamp: Option<Timestamp>,
+    /// optional, deposits and withdrawals without it use the default currency.
+    /// ignored for disputes, they act on the currency of the referenced deposit
+    #[serde(default)]
+    pub currency: Currency,
+    /// receiving client of transfers, empty for every other kind
+    #[serde(default)]
+    pub to_client: Option<ClientId>,
 }
 
 #[cfg(test)]
@@ -30,7 +48,7 @@ mod test {
     use csv::ByteRecord;
     use expect_test::expect;
 
-    use crate::common::amount::Amount;
+    use crate::{common::amount::Amount, Currency};
 
     use super::{Transaction, TransactionKind};
 
@@ -40,32 +58,56 @@ mod test {
             amount: Some(Amount::from_str("5.5").unwrap()),
             client_id: 6,
             tx_id: 5,
+            timestamp: Some("2024-03-01T12:30:00Z".parse().unwrap()),
+            currency: "EUR".parse().unwrap(),
+            to_client: None,
         };
         let withdrawal = Transaction {
             kind: TransactionKind::Withdraw,
             amount: Some(Amount::default()),
             client_id: 6,
             tx_id: 5,
+            timestamp: None,
+            currency: Currency::default(),
+            to_client: None,
         };
         let dispute = Transaction {
             kind: TransactionKind::Dispute,
             amount: Default::default(),
             client_id: 6,
             tx_id: 5,
+            timestamp: None,
+            currency: Currency::default(),
+            to_client: None,
         };
         let resolve = Transaction {
             kind: TransactionKind::Resolve,
             amount: Default::default(),
             client_id: 6,
             tx_id: 5,
+            timestamp: None,
+            currency: Currency::default(),
+            to_client: None,
         };
         let chargeback = Transaction {
             kind: TransactionKind::Chargeback,
             amount: Default::default(),
             client_id: 6,
             tx_id: 5,
+            timestamp: None,
+            currency: Currency::default(),
+            to_client: None,
         };
-        vec![deposit, withdrawal, dispute, resolve, chargeback]
+        let transfer = Transaction {
+            kind: TransactionKind::Transfer,
+            amount: Some(Amount::from_str("1.25").unwrap()),
+            client_id: 6,
+            tx_id: 6,
+            timestamp: None,
+            currency: Currency::default(),
+            to_client: Some(7),
+        };
+        vec![deposit, withdrawal, dispute, resolve, chargeback, transfer]
     }
 
     #[test]
@@ -95,16 +137,36 @@ mod test {
         }
         assert_eq!(&records, &example_data);
     }
+    #[test]
+    fn optional_timestamp() {
+        let input = "type, client, tx, amount, timestamp
+deposit, 1, 1, 1.5, 2024-03-01T14:30:00+02:00
+deposit, 1, 2, 1.5, 1709296200
+deposit, 1, 3, 1.5,
+deposit, 1, 4, 1.5";
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .from_reader(input.as_bytes());
+        let timestamps: Vec<_> = reader
+            .deserialize::<Transaction>()
+            .map(|x| x.unwrap().timestamp.map(|x| x.to_rfc3339()))
+            .collect();
+        let expected = Some("2024-03-01T12:30:00+00:00".to_string());
+        assert_eq!(timestamps, vec![expected.clone(), expected, None, None]);
+    }
+
     #[test]
     fn output_sample() {
         let example_data = example_data();
         let expected = expect![[r#"
-            type,client,tx,amount
-            deposit,6,5,5.5
-            withdrawal,6,5,0
-            dispute,6,5,
-            resolve,6,5,
-            chargeback,6,5,
+            type,client,tx,amount,timestamp,currency,to_client
+            deposit,6,5,5.5,2024-03-01T12:30:00Z,EUR,
+            withdrawal,6,5,0,,,
+            dispute,6,5,,,,
+            resolve,6,5,,,,
+            chargeback,6,5,,,,
+            transfer,6,6,1.25,,,7
         "#]];
         let mut buf = Vec::new();
         // write example records into provided `Writer`
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+use super::transaction::TransactionKind;
+use crate::{
+    core::{
+        parking::{ParkedSettlement, Unmatched},
+        transaction::SettlementTransaction,
+    },
+    Amount, ClientId, Timestamp, TxId,
+};
+
+/// One row of the unmatched settlements report.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct UnmatchedRecord {
+    pub client: ClientId,
+    pub tx: TxId,
+    #[serde(rename = "type")]
+    pub kind: TransactionKind,
+    /// empty for settlements of the whole transaction
+    #[serde(default, with = "super::amount::my_amount_opt")]
+    pub amount: Option<Amount>,
+    /// latest timestamp seen when it was parked
+    #[serde(default, with = "super::timestamp::my_timestamp_opt")]
+    pub timestamp: Option<Timestamp>,
+    pub reason: Unmatched,
+}
+
+impl From<(&ParkedSettlement, Unmatched)> for UnmatchedRecord {
+    fn from((parked, reason): (&ParkedSettlement, Unmatched)) -> Self {
+        UnmatchedRecord {
+            client: parked.client,
+            tx: parked.tx,
+            kind: match parked.kind {
+                SettlementTransaction::Dispute => TransactionKind::Dispute,
+                SettlementTransaction::Resolve => TransactionKind::Resolve,
+                SettlementTransaction::Chargeback => TransactionKind::Chargeback,
+                SettlementTransaction::Reversal => TransactionKind::ChargebackReversal,
+            },
+            amount: parked.amount,
+            timestamp: parked.timestamp,
+            reason,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use expect_test::expect;
+
+    use crate::{
+        core::{
+            parking::{ParkedSettlement, Unmatched},
+            transaction::SettlementTransaction,
+        },
+        Amount,
+    };
+
+    use super::UnmatchedRecord;
+
+    #[test]
+    fn output_sample() {
+        let parked = ParkedSettlement {
+            client: 2,
+            tx: 7,
+            kind: SettlementTransaction::Reversal,
+            amount: Some(Amount::from_str("2.5").unwrap()),
+            seq: 4,
+            timestamp: Some("2024-03-01T12:30:00Z".parse().unwrap()),
+        };
+        let records = [
+            UnmatchedRecord::from((&parked, Unmatched::Expired)),
+            UnmatchedRecord::from((
+                &ParkedSettlement {
+                    kind: SettlementTransaction::Dispute,
+                    amount: None,
+                    timestamp: None,
+                    ..parked
+                },
+                Unmatched::Pending,
+            )),
+        ];
+        let expected = expect![[r#"
+            client,tx,type,amount,timestamp,reason
+            2,7,chargeback_reversal,2.5,2024-03-01T12:30:00Z,expired
+            2,7,dispute,,,pending
+        "#]];
+        let mut buf = Vec::new();
+        {
+            let mut wtr = csv::Writer::from_writer(&mut buf);
+            for record in &records {
+                wtr.serialize(record).unwrap();
+            }
+            wtr.flush().unwrap();
+        }
+        let buf = String::from_utf8(buf).unwrap();
+        expected.assert_eq(&buf);
+    }
+}
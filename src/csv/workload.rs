@@ -0,0 +1,463 @@
+use std::{collections::VecDeque, io::Write};
+
+use chrono::TimeDelta;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::transaction::{Transaction, TransactionKind};
+use crate::{Amount, ClientId, Currency, Timestamp, TxId};
+
+/// How many deposits/disputes are remembered as targets for later disputes/settlements,
+/// keeps memory usage flat no matter how many rows are generated.
+const WINDOW: usize = 4096;
+
+/// a deposit or transfer with the amount a settlement of it can cover
+type Target = (ClientId, TxId, Amount);
+
+/// Rates are probabilities and must be within `0..=1`.
+#[derive(Debug, Clone)]
+pub struct WorkloadConfig {
+    pub seed: u64,
+    pub clients: ClientId,
+    pub transactions: u64,
+    /// share of rows that are withdrawals
+    pub withdrawal_rate: f64,
+    /// share of rows that transfer funds to another client
+    pub transfer_rate: f64,
+    /// share of rows that dispute an earlier deposit
+    pub dispute_rate: f64,
+    /// share of rows that settle an open dispute
+    pub settle_rate: f64,
+    /// share of settlements that are chargebacks instead of resolves
+    pub chargeback_rate: f64,
+    /// share of rows that reverse an earlier chargeback
+    pub reversal_rate: f64,
+    /// share of disputes and settlements covering half of the amount, the other half of a
+    /// partial settlement is settled later
+    pub partial_rate: f64,
+    /// share of rows that can't be parsed into a valid transaction
+    pub malformed_rate: f64,
+    /// share of deposits whose dispute is emitted before the deposit itself
+    pub out_of_order_rate: f64,
+    /// when set rows get increasing timestamps starting here, 1 to 60 seconds apart
+    pub start_time: Option<Timestamp>,
+    /// deposits, withdrawals and transfers pick one of these or the default currency
+    pub currencies: Vec<Currency>,
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            clients: 1000,
+            transactions: 10_000,
+            withdrawal_rate: 0.3,
+            transfer_rate: 0.0,
+            dispute_rate: 0.05,
+            settle_rate: 0.05,
+            chargeback_rate: 0.2,
+            reversal_rate: 0.0,
+            partial_rate: 0.0,
+            malformed_rate: 0.01,
+            out_of_order_rate: 0.0,
+            start_time: None,
+            currencies: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum WorkloadRow {
+    Transaction(Transaction),
+    Malformed([String; 4]),
+}
+
+/// Reproducible stream of transaction rows, the same config always yields the same rows.
+pub struct Workload {
+    config: WorkloadConfig,
+    rng: ChaCha8Rng,
+    emitted: u64,
+    next_tx: TxId,
+    deposits: VecDeque<Target>,
+    disputed: VecDeque<Target>,
+    charged_back: VecDeque<(ClientId, TxId)>,
+    delayed: Option<Transaction>,
+    clock: Option<Timestamp>,
+}
+
+impl Workload {
+    pub fn new(config: WorkloadConfig) -> Self {
+        Self {
+            rng: ChaCha8Rng::seed_from_u64(config.seed),
+            clock: config.start_time,
+            config,
+            emitted: 0,
+            next_tx: 1,
+            deposits: VecDeque::with_capacity(WINDOW),
+            disputed: VecDeque::with_capacity(WINDOW),
+            charged_back: VecDeque::with_capacity(WINDOW),
+            delayed: None,
+        }
+    }
+
+    fn fresh_tx(&mut self) -> TxId {
+        let tx = self.next_tx;
+        self.next_tx = self.next_tx.wrapping_add(1);
+        tx
+    }
+
+    fn client(&mut self) -> ClientId {
+        self.rng.gen_range(1..=self.config.clients.max(1))
+    }
+
+    fn amount(&mut self) -> Amount {
+        let ten_thousandths: u64 = self.rng.gen_range(1..=10_000_000);
+        let formatted = format!(
+            "{}.{:04}",
+            ten_thousandths / 10_000,
+            ten_thousandths % 10_000
+        );
+        Amount::from_str(&formatted).expect("formatted amount is always valid")
+    }
+
+    fn currency(&mut self) -> Currency {
+        // no draw without currencies, so the rows of such configs don't change
+        match self.config.currencies.len() {
+            0 => Currency::default(),
+            n => match self.rng.gen_range(0..=n) {
+                0 => Currency::default(),
+                i => self.config.currencies[i - 1],
+            },
+        }
+    }
+
+    /// half of `amount` for a partial dispute or settlement, rounded to the four decimals
+    /// of the input
+    fn partial(&mut self, amount: Amount) -> Option<Amount> {
+        (self.config.partial_rate > 0.0 && self.rng.gen_bool(self.config.partial_rate))
+            .then(|| {
+                let half = format!("{:.4}", amount / 2);
+                Amount::from_str(&half).expect("formatted amount is always valid")
+            })
+            .filter(|x| *x > Amount::ZERO)
+    }
+
+    fn remember<T>(queue: &mut VecDeque<T>, entry: T) {
+        if queue.len() == WINDOW {
+            queue.pop_front();
+        }
+        queue.push_back(entry);
+    }
+
+    fn deposit(&mut self) -> Transaction {
+        let client_id = self.client();
+        let tx_id = self.fresh_tx();
+        let amount = self.amount();
+        let deposit = Transaction {
+            kind: TransactionKind::Deposit,
+            client_id,
+            tx_id,
+            amount: Some(amount),
+            timestamp: None,
+            currency: self.currency(),
+            to_client: None,
+        };
+        // the last row can't be followed by the delayed deposit
+        let last = self.emitted >= self.config.transactions;
+        if !last && self.rng.gen_bool(self.config.out_of_order_rate) {
+            self.delayed = Some(deposit);
+            Self::remember(&mut self.disputed, (client_id, tx_id, amount));
+            return settlement(TransactionKind::Dispute, client_id, tx_id, None);
+        }
+        Self::remember(&mut self.deposits, (client_id, tx_id, amount));
+        deposit
+    }
+
+    fn malformed(&mut self) -> [String; 4] {
+        let client = self.client().to_string();
+        let tx = self.fresh_tx().to_string();
+        match self.rng.gen_range(0..5) {
+            0 => ["deposit".into(), client, tx, String::new()],
+            1 => ["deposit".into(), client, tx, "-1.5".into()],
+            2 => ["refund".into(), client, tx, "1.0".into()],
+            3 => ["withdrawal".into(), "client".into(), tx, "1.0".into()],
+            _ => ["deposit".into(), client, tx, "1.0.0".into()],
+        }
+    }
+}
+
+fn settlement(
+    kind: TransactionKind,
+    client_id: ClientId,
+    tx_id: TxId,
+    amount: Option<Amount>,
+) -> Transaction {
+    Transaction {
+        kind,
+        client_id,
+        tx_id,
+        amount,
+        timestamp: None,
+        currency: Currency::default(),
+        to_client: None,
+    }
+}
+
+impl Iterator for Workload {
+    type Item = WorkloadRow;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.emitted >= self.config.transactions {
+            return None;
+        }
+        self.emitted += 1;
+        let mut row = self.row();
+        if let (Some(clock), WorkloadRow::Transaction(tx)) = (&mut self.clock, &mut row) {
+            tx.timestamp = Some(*clock);
+            *clock += TimeDelta::seconds(self.rng.gen_range(1..=60));
+        }
+        Some(row)
+    }
+}
+
+impl Workload {
+    fn row(&mut self) -> WorkloadRow {
+        if let Some(deposit) = self.delayed.take() {
+            return WorkloadRow::Transaction(deposit);
+        }
+
+        let config = &self.config;
+        let mut roll: f64 = self.rng.gen();
+        if roll < config.malformed_rate {
+            return WorkloadRow::Malformed(self.malformed());
+        }
+        roll -= config.malformed_rate;
+        if roll < config.dispute_rate && !self.deposits.is_empty() {
+            let idx = self.rng.gen_range(0..self.deposits.len());
+            let (client, tx, amount) = self
+                .deposits
+                .swap_remove_back(idx)
+                .expect("index is in range");
+            let partial = self.partial(amount);
+            Self::remember(&mut self.disputed, (client, tx, partial.unwrap_or(amount)));
+            let tx = settlement(TransactionKind::Dispute, client, tx, partial);
+            return WorkloadRow::Transaction(tx);
+        }
+        roll -= config.dispute_rate;
+        if roll < config.settle_rate && !self.disputed.is_empty() {
+            let (client, tx, disputed) = self.disputed.pop_front().expect("queue isn't empty");
+            let kind = if self.rng.gen_bool(config.chargeback_rate) {
+                Self::remember(&mut self.charged_back, (client, tx));
+                TransactionKind::Chargeback
+            } else {
+                TransactionKind::Resolve
+            };
+            let partial = self.partial(disputed);
+            if let Some(settled) = partial {
+                Self::remember(&mut self.disputed, (client, tx, disputed - settled));
+            }
+            return WorkloadRow::Transaction(settlement(kind, client, tx, partial));
+        }
+        roll -= config.settle_rate;
+        if roll < config.reversal_rate && !self.charged_back.is_empty() {
+            let (client, tx) = self.charged_back.pop_front().expect("queue isn't empty");
+            let tx = settlement(TransactionKind::ChargebackReversal, client, tx, None);
+            return WorkloadRow::Transaction(tx);
+        }
+        roll -= config.reversal_rate;
+        if roll < config.withdrawal_rate {
+            let client_id = self.client();
+            let tx = Transaction {
+                kind: TransactionKind::Withdraw,
+                client_id,
+                tx_id: self.fresh_tx(),
+                amount: Some(self.amount()),
+                timestamp: None,
+                currency: self.currency(),
+                to_client: None,
+            };
+            return WorkloadRow::Transaction(tx);
+        }
+        roll -= config.withdrawal_rate;
+        if roll < config.transfer_rate {
+            let (client_id, to) = (self.client(), self.client());
+            let (tx_id, amount) = (self.fresh_tx(), self.amount());
+            Self::remember(&mut self.deposits, (client_id, tx_id, amount));
+            let tx = Transaction {
+                kind: TransactionKind::Transfer,
+                client_id,
+                tx_id,
+                amount: Some(amount),
+                timestamp: None,
+                currency: self.currency(),
+                to_client: Some(to),
+            };
+            return WorkloadRow::Transaction(tx);
+        }
+        WorkloadRow::Transaction(self.deposit())
+    }
+}
+
+/// Streams a generated workload as CSV in the format accepted by [`super::read_from_reader`].
+pub fn write_workload<O: Write>(config: WorkloadConfig, out: O) -> Result<(), csv::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b',')
+        .has_headers(false)
+        .flexible(true)
+        .double_quote(false)
+        .from_writer(out);
+
+    writer.write_record([
+        "type",
+        "client",
+        "tx",
+        "amount",
+        "timestamp",
+        "currency",
+        "to_client",
+    ])?;
+    for row in Workload::new(config) {
+        match row {
+            WorkloadRow::Transaction(tx) => writer.serialize(&tx)?,
+            WorkloadRow::Malformed(record) => writer.write_record(&record)?,
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use expect_test::expect;
+
+    use super::{write_workload, Workload, WorkloadConfig, WorkloadRow};
+    use crate::csv::{read_from_reader, transaction::TransactionKind};
+
+    fn config() -> WorkloadConfig {
+        WorkloadConfig {
+            seed: 42,
+            clients: 3,
+            transactions: 12,
+            dispute_rate: 0.2,
+            settle_rate: 0.2,
+            chargeback_rate: 0.5,
+            malformed_rate: 0.1,
+            out_of_order_rate: 0.1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reproducible() {
+        let first: Vec<_> = Workload::new(config()).collect();
+        let second: Vec<_> = Workload::new(config()).collect();
+        assert_eq!(first.len(), 12);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn delayed_deposits_are_emitted() {
+        let workload = WorkloadConfig {
+            transactions: 101,
+            malformed_rate: 0.0,
+            out_of_order_rate: 1.0,
+            ..config()
+        };
+        let rows: Vec<_> = Workload::new(workload)
+            .map(|x| match x {
+                WorkloadRow::Transaction(tx) => tx,
+                WorkloadRow::Malformed(_) => unreachable!("malformed rate is 0"),
+            })
+            .collect();
+        assert_eq!(rows.len(), 101);
+        for tx in rows.iter().filter(|x| x.kind == TransactionKind::Dispute) {
+            assert!(
+                rows.iter().any(|x| x.kind == TransactionKind::Deposit
+                    && (x.client_id, x.tx_id) == (tx.client_id, tx.tx_id)),
+                "orphaned dispute of {}",
+                tx.tx_id
+            );
+        }
+    }
+
+    #[test]
+    fn partial_amounts_have_four_decimals() {
+        let workload = WorkloadConfig {
+            transactions: 1000,
+            partial_rate: 1.0,
+            ..config()
+        };
+        let mut partial = 0;
+        for row in Workload::new(workload) {
+            let WorkloadRow::Transaction(tx) = row else {
+                continue;
+            };
+            let Some(amount) = tx.amount else {
+                continue;
+            };
+            partial += usize::from(tx.kind != TransactionKind::Deposit);
+            let written = amount.to_string();
+            let decimals = written.split_once('.').map_or(0, |(_, x)| x.len());
+            assert!(decimals <= 4, "{written}");
+        }
+        assert!(partial > 0);
+    }
+
+    #[test]
+    fn readable_output() {
+        let workload = WorkloadConfig {
+            transactions: 1000,
+            ..config()
+        };
+        let malformed = Workload::new(workload.clone())
+            .filter(|x| matches!(x, WorkloadRow::Malformed(_)))
+            .count();
+        let mut buf = Vec::new();
+        write_workload(workload, &mut buf).unwrap();
+        let parsed = read_from_reader(buf.as_slice()).unwrap().count();
+        assert!(malformed > 0);
+        // a few malformed rows (e.g. negative amounts) only fail later, in `Transaction::try_from`
+        assert!(parsed >= 1000 - malformed && parsed < 1000);
+    }
+
+    #[test]
+    fn output_sample() {
+        let expected = expect![[r#"
+            type,client,tx,amount,timestamp,currency,to_client
+            withdrawal,1,1,772.3134,,,
+            withdrawal,2,2,308.0406,,,
+            deposit,2,3,825.3662,,,
+            withdrawal,3,4,179.1365,,,
+            withdrawal,1,5,269.0852,,,
+            deposit,1,6,38.4542,,,
+            deposit,2,7,541.3467,,,
+            withdrawal,3,8,99.8418,,,
+            dispute,2,3,,,,
+            withdrawal,1,9,737.2733,,,
+            dispute,2,7,,,,
+            resolve,2,3,,,,
+        "#]];
+        let mut buf = Vec::new();
+        write_workload(config(), &mut buf).unwrap();
+        expected.assert_eq(&String::from_utf8(buf).unwrap());
+    }
+
+    #[test]
+    fn timestamped_output_sample() {
+        let config = WorkloadConfig {
+            transactions: 4,
+            start_time: Some("2024-03-01T00:00:00Z".parse().unwrap()),
+            ..config()
+        };
+        let expected = expect![[r#"
+            type,client,tx,amount,timestamp,currency,to_client
+            withdrawal,1,1,772.3134,2024-03-01T00:00:00Z,,
+            withdrawal,1,2,308.0406,2024-03-01T00:00:21Z,,
+            withdrawal,3,3,378.5456,2024-03-01T00:01:10Z,,
+            withdrawal,2,4,179.1365,2024-03-01T00:01:46Z,,
+        "#]];
+        let mut buf = Vec::new();
+        write_workload(config, &mut buf).unwrap();
+        expected.assert_eq(&String::from_utf8(buf).unwrap());
+    }
+}
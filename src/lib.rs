@@ -1,11 +1,19 @@
 mod common;
 
-pub use common::{amount::Amount, ClientId, TxId};
+pub use common::{amount::Amount, currency::Currency, ClientId, Timestamp, TxId};
 
 pub mod core;
 mod csv;
 pub use csv::{
-    account::Account as CsvAccount,
-    dump_to_csv, read_from_file,
+    account::{Account as CsvAccount, Columns as CsvColumns},
+    dump_exposure_to_csv, dump_fees_to_csv, dump_history_to_csv, dump_liabilities_to_csv,
+    dump_to_csv, dump_unmatched_to_csv,
+    exposure::{ExposureRecord as CsvExposureRecord, LiabilityRecord as CsvLiabilityRecord},
+    fees::FeeRecord as CsvFeeRecord,
+    history::{HistoryKind as CsvHistoryKind, HistoryRecord as CsvHistoryRecord},
+    limits::{read_limits, LimitsRecord as CsvLimitsRecord},
+    read_from_file, read_from_reader, read_records, read_records_from_file,
     transaction::{Transaction as CsvTransaction, TransactionKind as CsvTransactionKind},
+    unmatched::UnmatchedRecord as CsvUnmatchedRecord,
+    workload::{write_workload, Workload, WorkloadConfig, WorkloadRow},
 };
@@ -1,26 +1,533 @@
-use std::io::{stdout, Error};
+use std::{
+    fs::File,
+    io::{stdout, BufWriter, Error},
+    num::{NonZeroU64, NonZeroUsize},
+    path::PathBuf,
+    process::ExitCode,
+};
 
+use chrono::TimeDelta;
+use clap::{Args, Parser, Subcommand};
+use tracing::warn;
+use tracing_subscriber::filter::LevelFilter;
 use transactions::{
-    core::{ledger::Ledger, transaction::Transaction},
-    dump_to_csv, read_from_file,
+    core::{
+        events::JsonLines,
+        fees::{FeeSchedule, FeeTier},
+        fraud::{FraudCheck, FraudPolicy, TooManyDisputes, WithdrawalAfterDeposit},
+        index::disk::DiskIndex,
+        interest::{InterestPolicy, RateTier},
+        ledger::{Errors, Ledger},
+        lifecycle::{DisputeLifecycle, UnfreezePolicy},
+        limits::{DisputeOverdraft, LimitRules, Limits},
+        parking::ParkingPolicy,
+        projection::read_events,
+        retention::{DisputeWindow, RetentionPolicy},
+    },
+    dump_exposure_to_csv, dump_fees_to_csv, dump_history_to_csv, dump_liabilities_to_csv,
+    dump_to_csv, dump_unmatched_to_csv, read_limits, read_records_from_file, write_workload,
+    Amount, CsvColumns, Currency, Timestamp, WorkloadConfig,
 };
 
-fn main() -> Result<(), Error> {
-    let csv_path = std::env::args()
-        .nth(1)
-        .expect("Expected a CSV filename, run with `cargo run -- transactions.csv`");
+#[derive(Parser)]
+#[command(
+    about = "Simple transaction system, reads transactions from a CSV file and prints account balances",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    process: Process,
+    /// Log ledger decisions to stderr up to this level (off, error, warn, info, debug, trace)
+    #[arg(long, global = true, default_value = "warn")]
+    log_level: LevelFilter,
+    /// Write every column of the account report, not only those of the features in use
+    #[arg(long, global = true)]
+    all_columns: bool,
+    /// Log as JSON lines instead of plain text
+    #[arg(long, global = true)]
+    log_json: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Write a reproducible synthetic transactions CSV to stdout
+    Generate(Generate),
+    /// Process transactions and write the accounts with negative balances to stdout instead of all accounts
+    Exposure(Box<Exposure>),
+    /// Rebuild the accounts from an event log written by `--events` and write them to stdout
+    Replay(Replay),
+}
+
+#[derive(Args)]
+struct Replay {
+    /// NDJSON file with ledger events
+    events: PathBuf,
+}
+
+#[derive(Args)]
+struct Exposure {
+    #[command(flatten)]
+    process: Process,
+    /// Write the ledger liabilities per currency, split into deposits and chargebacks, as CSV to this file
+    #[arg(long)]
+    liabilities: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct Process {
+    /// CSV file with transactions
+    #[arg(required = true)]
+    input: Option<PathBuf>,
+    /// Check ledger invariants after processing and exit with an error on violation
+    #[arg(long)]
+    verify: bool,
+    /// Print run statistics (records read, applied/ignored per reason, totals, throughput) to stderr
+    #[arg(long)]
+    summary: bool,
+    /// Write the run statistics as JSON to this file
+    #[arg(long)]
+    summary_json: Option<PathBuf>,
+    /// Write every balance and status change as a JSON line (NDJSON) to this file
+    #[arg(long)]
+    events: Option<PathBuf>,
+    /// Serve Prometheus metrics on `http://ADDR/metrics` while processing
+    #[cfg(feature = "metrics")]
+    #[arg(long)]
+    metrics_addr: Option<std::net::SocketAddr>,
+    /// Write Prometheus metrics to this file after processing
+    #[cfg(feature = "metrics")]
+    #[arg(long)]
+    metrics_file: Option<PathBuf>,
+    /// Keep the transaction history in page files under this directory instead of memory
+    #[arg(long)]
+    index_dir: Option<PathBuf>,
+    /// Number of index pages (4096 tx ids each) cached in memory when using `--index-dir`
+    #[arg(long, default_value_t = NonZeroUsize::new(256).unwrap())]
+    index_cache_pages: NonZeroUsize,
+    /// Remove the pages left in `--index-dir` by a previous run before starting
+    #[arg(long, requires = "index_dir")]
+    index_reset: bool,
+    /// Deposits can only be disputed within this many subsequent transactions
+    #[arg(long, conflicts_with = "dispute_window_days")]
+    dispute_window: Option<u64>,
+    /// Deposits can only be disputed within this many days after their timestamp
+    #[arg(long, value_parser = days)]
+    dispute_window_days: Option<TimeDelta>,
+    /// Keep up to N settlements referencing unknown transactions until the transaction arrives
+    #[arg(long, default_value_t = 0)]
+    park_settlements: usize,
+    /// Drop parked settlements after this many subsequent transactions
+    #[arg(long, conflicts_with = "park_expiry_hours")]
+    park_expiry: Option<u64>,
+    /// Drop parked settlements this many hours after the latest timestamp seen when parking them
+    #[arg(long, value_parser = hours)]
+    park_expiry_hours: Option<TimeDelta>,
+    /// Write the settlements that never matched a transaction as CSV to this file
+    #[arg(long)]
+    unmatched: Option<PathBuf>,
+    /// Write the retained transaction history (with timestamps) as CSV to this file
+    #[arg(long)]
+    history: Option<PathBuf>,
+    /// Forget resolved and charged back deposits
+    #[arg(long)]
+    evict_settled: bool,
+    /// Evict expired/settled history every N transactions, `--summary` also reports the totals
+    #[arg(long)]
+    sweep_every: Option<NonZeroU64>,
+    /// Annual interest on positive balances above FROM, as `FROM:BPS` (e.g. `1000:150`), repeatable
+    #[arg(long, value_parser = parse_tier)]
+    interest_tier: Vec<RateTier>,
+    /// Annual interest in basis points charged on negative balances
+    #[arg(long, default_value_t = 0)]
+    debit_interest_bps: u32,
+    /// Days in a year used to get daily interest rates
+    #[arg(long, default_value_t = 365, value_parser = clap::value_parser!(u32).range(1..))]
+    interest_day_count: u32,
+    /// Post accrued interest after the last transaction, `interest` records post it during the run
+    #[arg(long)]
+    post_interest: bool,
+    /// Flat fee charged on top of every withdrawal
+    #[arg(long, default_value_t = Amount::ZERO, value_parser = fee)]
+    withdrawal_fee: Amount,
+    /// Fee on the part of a deposit above FROM, as `FROM:BPS` (e.g. `1000:50`), repeatable
+    #[arg(long, value_parser = parse_fee_tier)]
+    deposit_fee_tier: Vec<FeeTier>,
+    /// Penalty charged to an account for every chargeback
+    #[arg(long, default_value_t = Amount::ZERO, value_parser = fee)]
+    chargeback_penalty: Amount,
+    /// Write the fees charged per client and currency as CSV to this file
+    #[arg(long)]
+    fees: Option<PathBuf>,
+    /// Reject withdrawals and outgoing transfers above this amount
+    #[arg(long)]
+    max_withdrawal: Option<Amount>,
+    /// Reject withdrawals and outgoing transfers above this total per day
+    #[arg(long)]
+    max_daily_withdrawal: Option<Amount>,
+    /// Reject deposits once a client made this many within `--deposit-window-hours`
+    #[arg(long)]
+    max_deposits: Option<u32>,
+    #[arg(long, default_value = "24", value_parser = hours)]
+    deposit_window_hours: TimeDelta,
+    /// Reject withdrawals and outgoing transfers leaving less than this available
+    #[arg(long, allow_negative_numbers = true)]
+    min_balance: Option<Amount>,
+    /// Let withdrawals and outgoing transfers take available this far below zero
+    #[arg(long)]
+    credit_limit: Option<Amount>,
+    /// Let resolved deposits be disputed again
+    #[arg(long)]
+    allow_redispute: bool,
+    /// Unfreeze accounts once all their chargebacks were reversed
+    #[arg(long)]
+    unfreeze_on_reversal: bool,
+    /// Reject disputes that would take available past the credit limit instead of holding anyway
+    #[arg(long)]
+    reject_overdraft_disputes: bool,
+    /// CSV file with per-client limits overriding the defaults above
+    #[arg(long)]
+    limits: Option<PathBuf>,
+    /// Reject disputes of clients that already opened this many, each previous one scores 10
+    #[arg(long)]
+    max_disputes: Option<usize>,
+    /// Score withdrawals and outgoing transfers within `--fraud-window-hours` of a deposit of at least this
+    #[arg(long)]
+    flag_withdrawal_after: Option<Amount>,
+    #[arg(long, default_value = "24", value_parser = hours)]
+    fraud_window_hours: TimeDelta,
+    /// Reject transactions whose total fraud score reaches this
+    #[arg(long)]
+    fraud_reject_score: Option<u32>,
+    /// Hold transactions whose total fraud score reaches this until an `approve` or `decline` record
+    #[arg(long)]
+    review_score: Option<u32>,
+}
+
+fn parse_from_bps(value: &str) -> Result<(Amount, u32), String> {
+    let (from, bps) = value
+        .split_once(':')
+        .ok_or_else(|| "expected FROM:BPS".to_string())?;
+    Ok((
+        Amount::from_str(from).map_err(|e| e.to_string())?,
+        bps.parse()
+            .map_err(|e: std::num::ParseIntError| e.to_string())?,
+    ))
+}
+
+fn parse_tier(value: &str) -> Result<RateTier, String> {
+    parse_from_bps(value).map(|(from, bps)| RateTier { from, bps })
+}
+
+fn parse_fee_tier(value: &str) -> Result<FeeTier, String> {
+    let (from, bps) = parse_from_bps(value)?;
+    if bps > 10_000 {
+        return Err(format!("{bps} bps is more than the deposit"));
+    }
+    Ok(FeeTier { from, bps })
+}
+
+/// a fee or penalty, can't be negative
+fn fee(value: &str) -> Result<Amount, String> {
+    let fee = Amount::from_str(value).map_err(|e| e.to_string())?;
+    if fee < Amount::ZERO {
+        return Err(format!("{fee} is negative"));
+    }
+    Ok(fee)
+}
+
+#[derive(Args)]
+struct Generate {
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    #[arg(long, default_value_t = 1000)]
+    clients: u16,
+    /// Number of rows to generate
+    #[arg(long, default_value_t = 10_000)]
+    transactions: u64,
+    #[arg(long, default_value_t = 0.3, value_parser = rate)]
+    withdrawal_rate: f64,
+    /// Share of rows transferring funds to another client
+    #[arg(long, default_value_t = 0.0, value_parser = rate)]
+    transfer_rate: f64,
+    /// Share of rows opening a dispute
+    #[arg(long, default_value_t = 0.05, value_parser = rate)]
+    dispute_rate: f64,
+    /// Share of rows settling an open dispute
+    #[arg(long, default_value_t = 0.05, value_parser = rate)]
+    settle_rate: f64,
+    /// Share of settlements that are chargebacks
+    #[arg(long, default_value_t = 0.2, value_parser = rate)]
+    chargeback_rate: f64,
+    /// Share of rows reversing an earlier chargeback
+    #[arg(long, default_value_t = 0.0, value_parser = rate)]
+    reversal_rate: f64,
+    /// Share of disputes and settlements covering half of the amount
+    #[arg(long, default_value_t = 0.0, value_parser = rate)]
+    partial_rate: f64,
+    #[arg(long, default_value_t = 0.01, value_parser = rate)]
+    malformed_rate: f64,
+    /// Share of deposits whose dispute arrives before the deposit
+    #[arg(long, default_value_t = 0.0, value_parser = rate)]
+    out_of_order_rate: f64,
+    /// Give rows increasing timestamps starting at this RFC 3339 time
+    #[arg(long)]
+    start_time: Option<Timestamp>,
+    /// Currency deposits, withdrawals and transfers may use besides the unnamed one, repeatable
+    #[arg(long = "currency")]
+    currencies: Vec<Currency>,
+}
+
+/// a positive number of days
+fn days(s: &str) -> Result<TimeDelta, String> {
+    duration(s, TimeDelta::try_days)
+}
+
+/// a positive number of hours
+fn hours(s: &str) -> Result<TimeDelta, String> {
+    duration(s, TimeDelta::try_hours)
+}
 
-    let records = read_from_file(csv_path)?.filter_map(|x| Transaction::try_from(x).ok());
-    let mut ledger = Ledger::default();
-    for tx in records {
-        match ledger.handle_transaction(&tx) {
+fn duration(s: &str, unit: fn(i64) -> Option<TimeDelta>) -> Result<TimeDelta, String> {
+    let n: i64 = s.parse().map_err(|e| format!("{e}"))?;
+    if n <= 0 {
+        return Err(format!("{n} is not positive"));
+    }
+    unit(n).ok_or_else(|| format!("{n} is out of range"))
+}
+
+/// a probability, `0..=1`
+fn rate(s: &str) -> Result<f64, String> {
+    let rate: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&rate) {
+        Ok(rate)
+    } else {
+        Err(format!("{rate} is not within 0..=1"))
+    }
+}
+
+impl From<Generate> for WorkloadConfig {
+    fn from(args: Generate) -> Self {
+        WorkloadConfig {
+            seed: args.seed,
+            clients: args.clients,
+            transactions: args.transactions,
+            withdrawal_rate: args.withdrawal_rate,
+            transfer_rate: args.transfer_rate,
+            dispute_rate: args.dispute_rate,
+            settle_rate: args.settle_rate,
+            chargeback_rate: args.chargeback_rate,
+            reversal_rate: args.reversal_rate,
+            partial_rate: args.partial_rate,
+            malformed_rate: args.malformed_rate,
+            out_of_order_rate: args.out_of_order_rate,
+            start_time: args.start_time,
+            currencies: args.currencies,
+        }
+    }
+}
+
+/// Runs the input through a ledger configured by `args`, `report` writes the main output.
+fn process(
+    args: Process,
+    report: impl FnOnce(&Ledger) -> Result<(), Error>,
+) -> Result<ExitCode, Error> {
+    let input = args.input.expect("input is required without a subcommand");
+    let records = read_records_from_file(input)?;
+    let ledger = match args.index_dir {
+        Some(dir) => {
+            if args.index_reset {
+                DiskIndex::clear(&dir)?;
+            }
+            Ledger::with_index(DiskIndex::new(dir, args.index_cache_pages)?)
+        }
+        None => Ledger::default(),
+    };
+    let lifecycle = match args.allow_redispute {
+        true => DisputeLifecycle::default().with_redispute(),
+        false => DisputeLifecycle::default(),
+    };
+    let ledger = ledger.with_lifecycle(lifecycle.with_unfreeze(match args.unfreeze_on_reversal {
+        true => UnfreezePolicy::WhenReversed,
+        false => UnfreezePolicy::Never,
+    }));
+    let ledger = ledger.with_retention(RetentionPolicy {
+        dispute_window: args
+            .dispute_window
+            .map(DisputeWindow::Transactions)
+            .or(args.dispute_window_days.map(DisputeWindow::Duration)),
+        evict_settled: args.evict_settled,
+        sweep_every: args.sweep_every,
+    });
+    let ledger = ledger.with_parking(ParkingPolicy {
+        capacity: args.park_settlements,
+        expiry: args
+            .park_expiry
+            .map(DisputeWindow::Transactions)
+            .or(args.park_expiry_hours.map(DisputeWindow::Duration)),
+    });
+    let ledger = ledger.with_interest(InterestPolicy {
+        day_count: args.interest_day_count,
+        ..InterestPolicy::new(args.interest_tier, args.debit_interest_bps)
+    });
+    let ledger = ledger.with_fees(FeeSchedule::new(
+        args.withdrawal_fee,
+        args.deposit_fee_tier,
+        args.chargeback_penalty,
+    ));
+    let overrides = match args.limits {
+        Some(path) => read_limits(File::open(path)?)?,
+        None => Default::default(),
+    };
+    let ledger = ledger.with_limits(LimitRules {
+        defaults: Limits {
+            max_withdrawal: args.max_withdrawal,
+            max_daily_withdrawal: args.max_daily_withdrawal,
+            max_deposits: args.max_deposits,
+            min_balance: args.min_balance,
+            credit_limit: args.credit_limit,
+        },
+        overrides,
+        deposit_window: args.deposit_window_hours,
+        dispute_overdraft: if args.reject_overdraft_disputes {
+            DisputeOverdraft::Reject
+        } else {
+            DisputeOverdraft::Allow
+        },
+    });
+    let mut checks: Vec<Box<dyn FraudCheck>> = Vec::new();
+    if let Some(max) = args.max_disputes {
+        checks.push(Box::new(TooManyDisputes { max, points: 10 }));
+    }
+    if let Some(min_deposit) = args.flag_withdrawal_after {
+        checks.push(Box::new(WithdrawalAfterDeposit {
+            min_deposit,
+            window: args.fraud_window_hours,
+            score: 50,
+        }));
+    }
+    let mut ledger = ledger.with_fraud(FraudPolicy {
+        checks,
+        reject_score: args.fraud_reject_score,
+        review_score: args.review_score,
+    });
+    if let Some(path) = args.events {
+        ledger = ledger.with_subscriber(JsonLines::new(BufWriter::new(File::create(path)?)));
+    }
+    #[cfg(feature = "metrics")]
+    let exporter = args
+        .metrics_addr
+        .map(transactions::core::metrics::Exporter::bind)
+        .transpose()?;
+    for record in records {
+        #[cfg(feature = "metrics")]
+        if let Some(exporter) = &exporter {
+            if ledger.stats().records_read % 10_000 == 0 {
+                exporter.publish(&ledger);
+            }
+        }
+        match ledger.handle_record(record) {
             Ok(_) => {
                 // do something with the tx(e.g add it to persistent log)
             }
+            Err(Errors::Index(err)) => return Err(err),
             Err(_) => {
                 // error handling goes here
             }
         }
     }
-    dump_to_csv(ledger.entries(), stdout()).map_err(|err| err.into())
+    if args.post_interest {
+        ledger.post_interest();
+    }
+    ledger.flush_subscribers()?;
+    #[cfg(feature = "metrics")]
+    {
+        if let Some(exporter) = &exporter {
+            exporter.publish(&ledger);
+        }
+        if let Some(path) = args.metrics_file {
+            std::fs::write(path, ledger.render_metrics())?;
+        }
+    }
+    if args.summary {
+        eprintln!("{}", ledger.summary());
+        if args.sweep_every.is_some() {
+            eprintln!("{}", ledger.evicted());
+        }
+    }
+    if let Some(path) = args.summary_json {
+        serde_json::to_writer_pretty(File::create(path)?, &ledger.summary())?;
+    }
+    if args.verify {
+        let report = ledger.verify()?;
+        if !report.is_ok() {
+            eprint!("{report}");
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    if let Some(path) = args.fees {
+        dump_fees_to_csv(&ledger, File::create(path)?)?;
+    }
+    if let Some(path) = args.unmatched {
+        dump_unmatched_to_csv(&ledger, File::create(path)?)?;
+        let forgotten = ledger.forgotten_unmatched();
+        if forgotten > 0 {
+            warn!(
+                forgotten,
+                "older unmatched settlements were dropped from the list"
+            );
+        }
+    }
+    if let Some(path) = args.history {
+        dump_history_to_csv(&ledger, File::create(path)?)?;
+    }
+    report(&ledger)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn generate(args: Generate) -> Result<ExitCode, Error> {
+    write_workload(args.into(), stdout().lock())?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn exposure(args: Box<Exposure>) -> Result<ExitCode, Error> {
+    let liabilities = args.liabilities;
+    process(args.process, |ledger| {
+        if let Some(path) = liabilities {
+            dump_liabilities_to_csv(ledger, File::create(path)?)?;
+        }
+        Ok(dump_exposure_to_csv(ledger, stdout())?)
+    })
+}
+
+fn replay(args: Replay, columns: Option<CsvColumns>) -> Result<ExitCode, Error> {
+    let events = read_events(File::open(args.events)?).collect::<Result<Vec<_>, _>>()?;
+    let ledger = Ledger::from_events(events);
+    let columns = columns.unwrap_or_else(|| ledger.report_columns());
+    dump_to_csv(ledger.rows(), columns, stdout())?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> Result<ExitCode, Error> {
+    let cli = Cli::parse();
+    let logs = tracing_subscriber::fmt()
+        .with_max_level(cli.log_level)
+        .with_writer(std::io::stderr);
+    if cli.log_json {
+        logs.json().init();
+    } else {
+        logs.init();
+    }
+    let columns = cli.all_columns.then(CsvColumns::all);
+    match cli.command {
+        Some(Command::Generate(args)) => generate(args),
+        Some(Command::Exposure(args)) => exposure(args),
+        Some(Command::Replay(args)) => replay(args, columns),
+        None => process(cli.process, |ledger| {
+            let columns = columns.unwrap_or_else(|| ledger.report_columns());
+            Ok(dump_to_csv(ledger.rows(), columns, stdout())?)
+        }),
+    }
 }
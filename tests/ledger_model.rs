@@ -0,0 +1,347 @@
+//! Differential tests: random transaction streams are rendered to CSV, pushed through
+//! `read_from_reader` + `Ledger` and compared against a straightforward reference model.
+//! On failure proptest shrinks the stream and prints it as a ready-to-use CSV file.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use proptest::{prelude::*, sample::Index};
+use transactions::{
+    core::{ledger::Ledger, transaction::Transaction},
+    read_from_reader, Amount, ClientId, CsvAccount, Currency, TxId,
+};
+
+const CLIENTS: ClientId = 4;
+
+#[derive(Debug, Clone)]
+enum Step {
+    Deposit {
+        client: ClientId,
+        amount: u32,
+    },
+    Withdraw {
+        client: ClientId,
+        amount: u32,
+    },
+    Settle {
+        kind: Settle,
+        target: Index,
+        foreign_client: bool,
+    },
+    Duplicate(Index),
+    /// the tx id of an earlier deposit/withdrawal used by the other kind
+    Reuse(Index),
+    Malformed(u8),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Settle {
+    Dispute,
+    Resolve,
+    Chargeback,
+}
+
+#[derive(Clone)]
+enum Op {
+    Deposit {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+    },
+    Withdraw {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+    },
+    Settle {
+        kind: Settle,
+        client: ClientId,
+        tx: TxId,
+    },
+    Malformed(String),
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Deposit { client, tx, amount } => write!(f, "deposit, {client}, {tx}, {amount}"),
+            Op::Withdraw { client, tx, amount } => {
+                write!(f, "withdrawal, {client}, {tx}, {amount}")
+            }
+            Op::Settle { kind, client, tx } => {
+                let kind = match kind {
+                    Settle::Dispute => "dispute",
+                    Settle::Resolve => "resolve",
+                    Settle::Chargeback => "chargeback",
+                };
+                write!(f, "{kind}, {client}, {tx},")
+            }
+            Op::Malformed(row) => f.write_str(row),
+        }
+    }
+}
+
+/// Materialized stream of transactions, `Debug` prints it as a CSV file so shrunk failures
+/// can be copied straight into a repro.
+#[derive(Clone)]
+struct Workload(Vec<Op>);
+
+impl Workload {
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("type, client, tx, amount\n");
+        for op in &self.0 {
+            csv.push_str(&op.to_string());
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+impl fmt::Debug for Workload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\n{}", self.to_csv())
+    }
+}
+
+fn amount(ten_thousandths: u32) -> Amount {
+    let formatted = format!(
+        "{}.{:04}",
+        ten_thousandths / 10_000,
+        ten_thousandths % 10_000
+    );
+    Amount::from_str(&formatted).unwrap()
+}
+
+fn materialize(steps: Vec<Step>) -> Workload {
+    let mut ops = Vec::with_capacity(steps.len());
+    // indexes into `ops` of every well-formed deposit/withdrawal
+    let mut normal = Vec::new();
+    let mut next_tx: TxId = 1;
+    let mut fresh_tx = || {
+        next_tx += 1;
+        next_tx - 1
+    };
+    for step in steps {
+        let op = match step {
+            Step::Deposit { client, amount: a } => {
+                normal.push(ops.len());
+                Op::Deposit {
+                    client,
+                    tx: fresh_tx(),
+                    amount: amount(a),
+                }
+            }
+            Step::Withdraw { client, amount: a } => {
+                normal.push(ops.len());
+                Op::Withdraw {
+                    client,
+                    tx: fresh_tx(),
+                    amount: amount(a),
+                }
+            }
+            Step::Settle {
+                kind,
+                target,
+                foreign_client,
+            } => {
+                let (client, tx) = match normal.get(target.index(normal.len().max(1))) {
+                    Some(&i) => match ops[i] {
+                        Op::Deposit { client, tx, .. } | Op::Withdraw { client, tx, .. } => {
+                            (client, tx)
+                        }
+                        _ => unreachable!(),
+                    },
+                    None => (1, fresh_tx()),
+                };
+                let client = if foreign_client {
+                    client % CLIENTS + 1
+                } else {
+                    client
+                };
+                Op::Settle { kind, client, tx }
+            }
+            Step::Duplicate(target) => match normal.get(target.index(normal.len().max(1))) {
+                Some(&i) => ops[i].clone(),
+                None => continue,
+            },
+            Step::Reuse(target) => match normal.get(target.index(normal.len().max(1))) {
+                Some(&i) => {
+                    normal.push(ops.len());
+                    match ops[i] {
+                        Op::Deposit { client, tx, amount } => Op::Withdraw { client, tx, amount },
+                        Op::Withdraw { client, tx, amount } => Op::Deposit { client, tx, amount },
+                        _ => unreachable!(),
+                    }
+                }
+                None => continue,
+            },
+            Step::Malformed(variant) => {
+                let tx = fresh_tx();
+                Op::Malformed(match variant % 5 {
+                    0 => format!("deposit, 1, {tx},"),
+                    1 => format!("deposit, 1, {tx}, -1.5"),
+                    2 => format!("withdrawal, 1, {tx}, 0"),
+                    3 => format!("refund, 1, {tx}, 1.0"),
+                    _ => format!("deposit, one, {tx}, 1.0"),
+                })
+            }
+        };
+        ops.push(op);
+    }
+    Workload(ops)
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    let client = 1..=CLIENTS;
+    // round amounts make boundary cases (withdrawing exactly what's available) likely
+    let amount = prop_oneof![(1..=5u32).prop_map(|x| x * 10_000), 1..=500_000u32];
+    let settle = prop_oneof![
+        Just(Settle::Dispute),
+        Just(Settle::Resolve),
+        Just(Settle::Chargeback)
+    ];
+    prop_oneof![
+        4 => (client.clone(), amount.clone())
+            .prop_map(|(client, amount)| Step::Deposit { client, amount }),
+        3 => (client, amount).prop_map(|(client, amount)| Step::Withdraw { client, amount }),
+        4 => (settle, any::<Index>(), prop::bool::weighted(0.1)).prop_map(
+            |(kind, target, foreign_client)| Step::Settle {
+                kind,
+                target,
+                foreign_client
+            }
+        ),
+        1 => any::<Index>().prop_map(Step::Duplicate),
+        1 => any::<Index>().prop_map(Step::Reuse),
+        1 => any::<u8>().prop_map(Step::Malformed),
+    ]
+}
+
+fn workload() -> impl Strategy<Value = Workload> {
+    prop::collection::vec(step(), 0..200).prop_map(materialize)
+}
+
+#[derive(PartialEq)]
+enum DepositState {
+    Applied,
+    Disputed,
+    Settled,
+}
+
+#[derive(Default)]
+struct ModelClient {
+    available: Amount,
+    held: Amount,
+    locked: bool,
+    deposits: BTreeMap<TxId, (Amount, DepositState)>,
+    withdrawals: BTreeSet<TxId>,
+}
+
+/// Reference implementation of the rules described in the README.
+#[derive(Default)]
+struct Model {
+    clients: BTreeMap<ClientId, ModelClient>,
+}
+
+impl Model {
+    fn apply(&mut self, op: &Op) {
+        match *op {
+            Op::Deposit { client, tx, amount } => {
+                let client = self.clients.entry(client).or_default();
+                if client.locked || client.deposits.contains_key(&tx) {
+                    return;
+                }
+                client.available += amount;
+                client.deposits.insert(tx, (amount, DepositState::Applied));
+            }
+            Op::Withdraw { client, tx, amount } => {
+                let Some(client) = self.clients.get_mut(&client) else {
+                    return;
+                };
+                if client.locked || !client.withdrawals.insert(tx) {
+                    return;
+                }
+                if client.available >= amount {
+                    client.available -= amount;
+                }
+            }
+            Op::Settle { kind, client, tx } => {
+                let Some(client) = self.clients.get_mut(&client) else {
+                    return;
+                };
+                let Some((amount, state)) = client.deposits.get_mut(&tx) else {
+                    return;
+                };
+                match (kind, &state) {
+                    (Settle::Dispute, DepositState::Applied) => {
+                        *state = DepositState::Disputed;
+                        client.available -= *amount;
+                        client.held += *amount;
+                    }
+                    (Settle::Resolve, DepositState::Disputed) => {
+                        *state = DepositState::Settled;
+                        client.available += *amount;
+                        client.held -= *amount;
+                    }
+                    (Settle::Chargeback, DepositState::Disputed) => {
+                        *state = DepositState::Settled;
+                        client.held -= *amount;
+                        client.locked = true;
+                    }
+                    _ => {}
+                }
+            }
+            Op::Malformed(_) => {}
+        }
+    }
+
+    fn accounts(&self) -> Vec<CsvAccount> {
+        self.clients
+            .iter()
+            .map(|(&client, state)| CsvAccount {
+                client,
+                available: state.available,
+                held: state.held,
+                total: state.available + state.held,
+                locked: state.locked,
+                currency: Currency::default(),
+                credit_used: (-state.available).max(Amount::ZERO),
+                pending_withdrawals: Amount::ZERO,
+                pending_deposits: Amount::ZERO,
+            })
+            .collect()
+    }
+}
+
+fn run_ledger(workload: &Workload) -> Result<Ledger, TestCaseError> {
+    let csv = workload.to_csv();
+    let records = read_from_reader(csv.as_bytes())
+        .unwrap()
+        .filter_map(|x| Transaction::try_from(x).ok());
+    let mut ledger = Ledger::default();
+    for tx in records {
+        let _ = ledger.handle_transaction(&tx);
+        let report = ledger.verify().unwrap();
+        prop_assert!(report.is_ok(), "{}", report);
+    }
+    Ok(ledger)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn ledger_matches_model(workload in workload()) {
+        let ledger = run_ledger(&workload)?;
+
+        let mut model = Model::default();
+        for op in &workload.0 {
+            model.apply(op);
+        }
+
+        let actual: Vec<CsvAccount> = ledger.rows().collect();
+        prop_assert_eq!(actual, model.accounts());
+    }
+}
@@ -0,0 +1,196 @@
+//! Generated workloads are pushed through a `Ledger` that records its events, the ledger
+//! rebuilt from the events alone has to end up in the same state.
+
+use std::fs::File;
+
+use proptest::prelude::*;
+use transactions::{
+    core::{
+        events::JsonLines,
+        fees::{FeeSchedule, FeeTier},
+        fraud::{ClientView, FraudCheck, FraudPolicy, Verdict},
+        interest::{InterestPolicy, RateTier},
+        ledger::Ledger,
+        lifecycle::{DisputeLifecycle, UnfreezePolicy},
+        limits::{LimitRules, Limits},
+        parking::ParkingPolicy,
+        projection::{read_events, EventLog},
+        transaction::Transaction,
+    },
+    Amount, Currency, Workload, WorkloadConfig, WorkloadRow,
+};
+
+/// Sends every fifth client transaction to review, nothing approves them.
+struct EveryFifth;
+
+impl FraudCheck for EveryFifth {
+    fn check(&self, tx: &Transaction, _: Option<ClientView<'_>>) -> Verdict {
+        let flagged = matches!(tx, Transaction::Normal { tx_id, .. } if tx_id % 5 == 0);
+        Verdict::Allow {
+            score: if flagged { 10 } else { 0 },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Setup {
+    workload: WorkloadConfig,
+    fees: bool,
+    interest: bool,
+    credit: bool,
+    review: bool,
+    unfreeze: bool,
+    parking: usize,
+}
+
+fn setup() -> impl Strategy<Value = Setup> {
+    (
+        any::<u64>(),
+        0.0..0.6,
+        0.0..0.2,
+        (0.0..0.1, 0.0..0.1, 0.0..0.5),
+        any::<[bool; 5]>(),
+        0..8usize,
+    )
+        .prop_map(
+            |(seed, chargeback_rate, out_of_order_rate, rates, flags, parking)| {
+                let (transfer_rate, reversal_rate, partial_rate) = rates;
+                let [fees, interest, credit, review, unfreeze] = flags;
+                Setup {
+                    workload: WorkloadConfig {
+                        seed,
+                        clients: 20,
+                        transactions: 500,
+                        dispute_rate: 0.1,
+                        settle_rate: 0.1,
+                        chargeback_rate,
+                        transfer_rate,
+                        reversal_rate,
+                        partial_rate,
+                        out_of_order_rate,
+                        start_time: Some("2024-01-01T22:00:00Z".parse().unwrap()),
+                        currencies: vec!["EUR".parse().unwrap()],
+                        ..Default::default()
+                    },
+                    fees,
+                    interest,
+                    credit,
+                    review,
+                    unfreeze,
+                    parking,
+                }
+            },
+        )
+}
+
+fn ledger(setup: &Setup) -> Ledger {
+    let mut ledger = Ledger::default()
+        .with_lifecycle(
+            DisputeLifecycle::default().with_unfreeze(if setup.unfreeze {
+                UnfreezePolicy::WhenReversed
+            } else {
+                UnfreezePolicy::Never
+            }),
+        )
+        .with_parking(ParkingPolicy {
+            capacity: setup.parking,
+            expiry: None,
+        });
+    if setup.fees {
+        ledger = ledger.with_fees(FeeSchedule::new(
+            Amount::from_num(0.25),
+            vec![FeeTier {
+                from: Amount::ZERO,
+                bps: 50,
+            }],
+            Amount::from_num(5),
+        ));
+    }
+    if setup.interest {
+        ledger = ledger.with_interest(InterestPolicy::new(
+            vec![RateTier {
+                from: Amount::ZERO,
+                bps: 5000,
+            }],
+            9000,
+        ));
+    }
+    if setup.credit {
+        ledger = ledger.with_limits(LimitRules {
+            defaults: Limits {
+                credit_limit: Some(Amount::from_num(100)),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+    }
+    if setup.review {
+        ledger = ledger.with_fraud(FraudPolicy {
+            checks: vec![Box::new(EveryFifth)],
+            reject_score: None,
+            review_score: Some(10),
+        });
+    }
+    ledger
+}
+
+fn assert_same_state(ledger: &Ledger, projected: &Ledger) {
+    let rows: Vec<_> = ledger.rows().collect();
+    assert_eq!(rows, projected.rows().collect::<Vec<_>>());
+    let mut currencies: Vec<Currency> = rows.iter().map(|x| x.currency).collect();
+    currencies.push(Currency::default());
+    for currency in &currencies {
+        assert_eq!(
+            ledger.liabilities(currency),
+            projected.liabilities(currency)
+        );
+        assert_eq!(ledger.revenue(currency), projected.revenue(currency));
+        assert_eq!(
+            ledger.interest_house(currency),
+            projected.interest_house(currency)
+        );
+    }
+    assert_eq!(
+        ledger.account_exposure().collect::<Vec<_>>(),
+        projected.account_exposure().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        ledger.liability_exposure().collect::<Vec<_>>(),
+        projected.liability_exposure().collect::<Vec<_>>()
+    );
+    let (summary, projected_summary) = (ledger.summary(), projected.summary());
+    assert_eq!(summary.frozen, projected_summary.frozen);
+    assert_eq!(summary.held, projected_summary.held);
+    assert_eq!(summary.charged_back, projected_summary.charged_back);
+    assert!(projected.verify().unwrap().is_ok());
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn fold_of_events_is_the_final_state(setup in setup()) {
+        let log = EventLog::default();
+        let stored = tempfile::NamedTempFile::new().unwrap();
+        let mut ledger = ledger(&setup)
+            .with_subscriber(log.clone())
+            .with_subscriber(JsonLines::new(stored.reopen().unwrap()));
+        for row in Workload::new(setup.workload.clone()) {
+            let WorkloadRow::Transaction(tx) = row else {
+                continue;
+            };
+            if let Ok(tx) = Transaction::try_from(tx) {
+                let _ = ledger.handle_transaction(&tx);
+            }
+        }
+        ledger.post_interest();
+        ledger.flush_subscribers().unwrap();
+
+        assert_same_state(&ledger, &Ledger::from_events(log.events()));
+        let events = read_events(File::open(stored.path()).unwrap())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        prop_assert_eq!(&events, &log.events());
+        assert_same_state(&ledger, &Ledger::from_events(events));
+    }
+}